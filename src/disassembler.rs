@@ -0,0 +1,174 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+use crate::{Executable, Instruction, Register, Symbol};
+
+
+#[derive(Debug, Clone, Copy)]
+pub enum Disassembled {
+    Instruction(Instruction),
+    Byte(u8),
+}
+
+
+impl Disassembled {
+    pub fn size(self) -> usize {
+        match self {
+            Self::Instruction(instr) => instr.to_id().size(),
+            Self::Byte(_) => 1,
+        }
+    }
+}
+
+
+pub struct Disassembler<'a> {
+    bytes: &'a [u8],
+    base: u16,
+    at: usize,
+}
+
+
+impl<'a> Disassembler<'a> {
+    pub fn new(bytes: &'a [u8], base: u16) -> Self {
+        Self { bytes, base, at: 0 }
+    }
+}
+
+
+impl Iterator for Disassembler<'_> {
+    type Item = (u16, Disassembled);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self.bytes.get(self.at..).filter(|rest| !rest.is_empty())?;
+        let addr = self.base.wrapping_add(self.at as u16);
+
+        let item = match Instruction::decode_from_iter(&mut rest.iter().copied()) {
+            Ok(instr) => Disassembled::Instruction(instr),
+            Err(_) => Disassembled::Byte(rest[0]),
+        };
+        self.at += item.size();
+
+        Some((addr, item))
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct DisassembledSegment {
+    pub addr: u16,
+    pub size: usize,
+    pub items: Vec<(u16, Disassembled)>,
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Disassembly {
+    pub entry: u16,
+    pub segments: Vec<DisassembledSegment>,
+    pub labels: BTreeMap<u16, String>,
+}
+
+
+impl Disassembly {
+    pub fn from_raw(bytes: &[u8]) -> Self {
+        Self::new(0x0000, &[(0x0000, bytes.len(), bytes)], &[])
+    }
+
+    pub fn from_executable(exe: &Executable) -> Self {
+        let segments = exe.segments.iter()
+            .map(|seg| (seg.addr, seg.size, seg.data.as_slice()))
+            .collect::<Vec<_>>();
+        Self::new(exe.entry, &segments, exe.symbols.as_deref().unwrap_or_default())
+    }
+
+    pub fn new(entry: u16, segments: &[(u16, usize, &[u8])], symbols: &[Symbol]) -> Self {
+        let segments = segments.iter()
+            .map(|&(addr, size, data)| DisassembledSegment { addr, size, items: Disassembler::new(data, addr).collect() })
+            .collect::<Vec<_>>();
+
+        let items = || segments.iter().flat_map(|seg| seg.items.iter().copied());
+        let instr_addrs = items()
+            .filter(|(_, item)| matches!(item, Disassembled::Instruction(_)))
+            .map(|(addr, _)| addr)
+            .collect::<HashSet<_>>();
+        // symbols can also point at the start of zeroed parts of segments
+        let bss_addrs = segments.iter()
+            .map(|seg| (seg.addr, seg.size, seg.items.iter().map(|(_, item)| item.size()).sum::<usize>()))
+            .filter(|(_, size, data_size)| size > data_size)
+            .map(|(addr, _, data_size)| addr.wrapping_add(data_size as u16));
+        let item_addrs = items().map(|(addr, _)| addr).chain(bss_addrs).collect::<HashSet<_>>();
+
+        let mut labels = BTreeMap::new();
+        for sym in symbols.iter().filter(|sym| item_addrs.contains(&sym.addr)) {
+            labels.entry(sym.addr).or_insert_with(|| sym.name.clone());
+        };
+        for (_, item) in items() {
+            if let Disassembled::Instruction(
+                Instruction::Set(Register::ServiceInstruction, target)
+                | Instruction::SetIfNotZero(Register::ServiceInstruction, target)
+                | Instruction::SetIfZero(Register::ServiceInstruction, target)
+            ) = item && instr_addrs.contains(&target) {
+                labels.entry(target).or_insert_with(|| format!("l_{target:0>4x}"));
+            };
+        };
+
+        Self { entry, segments, labels }
+    }
+}
+
+
+impl Display for Disassembly {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(label) = self.labels.get(&self.entry) {
+            writeln!(f, "/ entry: %{label}")?;
+        } else {
+            writeln!(f, "/ entry: #x{:0>4x}", self.entry)?;
+        };
+
+        // every segment is placed where it was, with its zeroed part (or the whole of it) left to bss
+        let mut in_bss = false;
+        for seg in self.segments.iter() {
+            writeln!(f)?;
+            writeln!(f, "/ segment at #x{:0>4x} ({} bytes)", seg.addr, seg.size)?;
+
+            if !seg.items.is_empty() {
+                if in_bss {
+                    writeln!(f, "!section text")?;
+                    in_bss = false;
+                };
+                writeln!(f, "!org #x{:0>4x}", seg.addr)?;
+            };
+
+            for (addr, item) in seg.items.iter() {
+                if let Some(label) = self.labels.get(addr) {
+                    writeln!(f, ":{label}")?;
+                };
+
+                match item {
+                    Disassembled::Instruction(instr @ (
+                        Instruction::Set(reg, target)
+                        | Instruction::SetIfNotZero(reg, target)
+                        | Instruction::SetIfZero(reg, target)
+                    )) if *reg == Register::ServiceInstruction && self.labels.contains_key(target) =>
+                        writeln!(f, "    {} {reg} %{}", instr.to_id(), self.labels[target])?,
+                    Disassembled::Instruction(instr) => writeln!(f, "    {instr}")?,
+                    Disassembled::Byte(b) => writeln!(f, "    !byte #x{b:0>2x}")?,
+                };
+            };
+
+            let data_size = seg.items.iter().map(|(_, item)| item.size()).sum::<usize>();
+            if seg.size > data_size {
+                if !in_bss {
+                    writeln!(f, "!section bss")?;
+                    in_bss = true;
+                };
+                writeln!(f, "!org #x{:0>4x}", seg.addr as usize + data_size)?;
+                if let Some(label) = self.labels.get(&seg.addr.wrapping_add(data_size as u16)) {
+                    writeln!(f, ":{label}")?;
+                };
+                writeln!(f, "    !bytes #x00 #d{}", seg.size - data_size)?;
+            };
+        };
+
+        Ok(())
+    }
+}
@@ -0,0 +1,272 @@
+use std::fmt::{Display, Formatter};
+
+pub const ISA_VERSION: u16 = 1;
+
+
+// layout (all integers are little endian):
+//   magic    4 bytes  "WTTE"
+//   isa      u16
+//   entry    u16
+//   count    u16      amount of sections
+//   section  * count
+//     kind   u8
+//     addr   u16      load address (0 for non-load sections)
+//     size   u32      size in memory (equals len for non-load sections)
+//     len    u32
+//     data   len bytes
+#[derive(Debug, Clone)]
+pub struct Executable {
+    pub isa_version: u16,
+    pub entry: u16,
+    pub segments: Vec<Segment>,
+    pub symbols: Option<Vec<Symbol>>,
+    pub debug: Option<Vec<u8>>,
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub addr: u16,
+    pub size: usize,
+    pub data: Vec<u8>,
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub addr: u16,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SectionKind {
+    Load,
+    Symbols,
+    Debug,
+}
+
+
+impl SectionKind {
+    fn code(self) -> u8 {
+        match self {
+            Self::Load => 0x01,
+            Self::Symbols => 0x02,
+            Self::Debug => 0x03,
+        }
+    }
+}
+
+
+impl TryFrom<u8> for SectionKind {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::Load),
+            0x02 => Ok(Self::Symbols),
+            0x03 => Ok(Self::Debug),
+            _ => Err(()),
+        }
+    }
+}
+
+
+impl Segment {
+    pub fn new(addr: u16, data: Vec<u8>) -> Self {
+        Self { addr, size: data.len(), data }
+    }
+
+    pub fn end(&self) -> usize {
+        self.addr as usize + self.size
+    }
+}
+
+
+impl Executable {
+    pub const MAGIC: [u8; 4] = *b"WTTE";
+
+    pub fn new(entry: u16, segments: Vec<Segment>) -> Self {
+        Self { isa_version: ISA_VERSION, entry, segments, symbols: None, debug: None }
+    }
+
+    pub fn is_executable(bytes: &[u8]) -> bool {
+        bytes.starts_with(&Self::MAGIC)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(Self::MAGIC);
+        bytes.extend(self.isa_version.to_le_bytes());
+        bytes.extend(self.entry.to_le_bytes());
+
+        let count = self.segments.len() + self.symbols.is_some() as usize + self.debug.is_some() as usize;
+        bytes.extend((count as u16).to_le_bytes());
+
+        let mut push_section = |kind: SectionKind, addr: u16, size: usize, data: &[u8]| {
+            bytes.push(kind.code());
+            bytes.extend(addr.to_le_bytes());
+            bytes.extend((size as u32).to_le_bytes());
+            bytes.extend((data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(data);
+        };
+
+        for seg in self.segments.iter() {
+            push_section(SectionKind::Load, seg.addr, seg.size, &seg.data);
+        };
+
+        if let Some(symbols) = &self.symbols {
+            let data = encode_symbols(symbols);
+            push_section(SectionKind::Symbols, 0, data.len(), &data);
+        };
+
+        if let Some(debug) = &self.debug {
+            push_section(SectionKind::Debug, 0, debug.len(), debug);
+        };
+
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ExecutableDecodingError> {
+        let mut reader = Reader::new(bytes);
+
+        if reader.take(4)? != Self::MAGIC {
+            return Err(ExecutableDecodingError::InvalidMagic);
+        };
+
+        let isa_version = reader.u16()?;
+        let entry = reader.u16()?;
+        let count = reader.u16()?;
+
+        let mut exe = Self { isa_version, entry, segments: Vec::new(), symbols: None, debug: None };
+        for _ in 0..count {
+            let kind = SectionKind::try_from(reader.u8()?).map_err(|()| ExecutableDecodingError::UnknownSection)?;
+            let addr = reader.u16()?;
+            let size = reader.u32()? as usize;
+            let len = reader.u32()? as usize;
+            let data = reader.take(len)?;
+
+            match kind {
+                SectionKind::Load => {
+                    if len > size || addr as usize + size > 0x1_0000 {
+                        return Err(ExecutableDecodingError::InvalidSegment { addr });
+                    };
+                    exe.segments.push(Segment { addr, size, data: data.to_vec() });
+                },
+                SectionKind::Symbols => { exe.symbols = Some(decode_symbols(data).ok_or(ExecutableDecodingError::InvalidSymbol)?); },
+                SectionKind::Debug => { exe.debug = Some(data.to_vec()); },
+            };
+        };
+
+        Ok(exe)
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<u16> {
+        self.symbols.as_ref()?.iter().find(|sym| sym.name == name).map(|sym| sym.addr)
+    }
+}
+
+
+// symbols as stored in a section: address, name length, name
+pub(crate) fn encode_symbols(symbols: &[Symbol]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for sym in symbols.iter() {
+        data.extend(sym.addr.to_le_bytes());
+        data.extend(encode_name(&sym.name));
+    };
+    data
+}
+
+
+pub(crate) fn decode_symbols(data: &[u8]) -> Option<Vec<Symbol>> {
+    let mut reader = Reader::new(data);
+    let mut symbols = Vec::new();
+    while !reader.is_empty() {
+        let addr = reader.u16().ok()?;
+        let name = reader.name().ok()??;
+        symbols.push(Symbol { name, addr });
+    };
+    Some(symbols)
+}
+
+
+// length followed by utf-8 bytes
+pub(crate) fn encode_name(name: &str) -> Vec<u8> {
+    let mut data = Vec::from((name.len() as u16).to_le_bytes());
+    data.extend_from_slice(name.as_bytes());
+    data
+}
+
+
+// ran out of bytes while reading
+pub(crate) struct EarlyEOB;
+
+
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, at: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.at >= self.bytes.len()
+    }
+
+    pub fn take(&mut self, n: usize) -> Result<&'a [u8], EarlyEOB> {
+        let slice = self.bytes.get(self.at..self.at + n).ok_or(EarlyEOB)?;
+        self.at += n;
+        Ok(slice)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, EarlyEOB> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, EarlyEOB> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, EarlyEOB> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    // none if not valid utf-8
+    pub fn name(&mut self) -> Result<Option<String>, EarlyEOB> {
+        let len = self.u16()? as usize;
+        Ok(String::from_utf8(self.take(len)?.to_vec()).ok())
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
+pub enum ExecutableDecodingError {
+    InvalidMagic,
+    EarlyEOB,
+    UnknownSection,
+    InvalidSegment { addr: u16 },
+    InvalidSymbol,
+}
+
+impl Display for ExecutableDecodingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidMagic => write!(f, "not a watto executable (invalid magic)"),
+            Self::EarlyEOB => write!(f, "too early end of byte stream"),
+            Self::UnknownSection => write!(f, "encountered a section of unknown kind"),
+            Self::InvalidSegment { addr } => write!(f, "segment at 0x{addr:0>4x} does not fit into address space"),
+            Self::InvalidSymbol => write!(f, "symbol name is not valid utf-8"),
+        }
+    }
+}
+
+impl std::error::Error for ExecutableDecodingError {}
+
+impl From<EarlyEOB> for ExecutableDecodingError {
+    fn from(_: EarlyEOB) -> Self {
+        Self::EarlyEOB
+    }
+}
@@ -0,0 +1,266 @@
+use std::fmt::{Display, Formatter};
+use crate::Register;
+use super::{Argument, InstructionDecodingError};
+
+
+// the one and only description of the instruction set,
+// everything else (encoding, decoding, assembler checks, reference) is derived from it
+//
+// group: name code "note" { code mnemonic Variant(field: Argument, ...) "semantics"; }
+macro_rules! isa {
+    ($(
+        $group:ident $group_code:literal $group_note:literal {
+            $( $code:literal $mnemonic:literal $name:ident $( ( $( $field:ident : $kind:ident ),* ) )? $summary:literal; )*
+        }
+    )*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum InstructionId {
+            $($( $name, )*)*
+        }
+
+
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub enum Instruction {
+            $($( $name $( ( $( operand_ty!($kind) ),* ) )?, )*)*
+        }
+
+
+        pub const ISA: &[Group] = &[
+            $(Group {
+                name: stringify!($group),
+                code: $group_code,
+                note: $group_note,
+                instructions: &[
+                    $(InstructionSpec {
+                        id: InstructionId::$name,
+                        mnemonic: $mnemonic,
+                        code: ($group_code << 4) | $code,
+                        arguments: &[$($( Argument::$kind ),*)?],
+                        summary: $summary,
+                    },)*
+                ],
+            },)*
+        ];
+
+
+        impl InstructionId {
+            pub const ALL: &[InstructionId] = &[$($( Self::$name, )*)*];
+
+            pub fn spec(self) -> &'static InstructionSpec {
+                match self {
+                    $($( Self::$name => &InstructionSpec {
+                        id: InstructionId::$name,
+                        mnemonic: $mnemonic,
+                        code: ($group_code << 4) | $code,
+                        arguments: &[$($( Argument::$kind ),*)?],
+                        summary: $summary,
+                    }, )*)*
+                }
+            }
+        }
+
+
+        impl TryFrom<u8> for InstructionId {
+            type Error = ();
+
+            fn try_from(value: u8) -> Result<Self, Self::Error> {
+                $($(
+                    if value == ($group_code << 4) | $code {
+                        return Ok(Self::$name);
+                    };
+                )*)*
+                Err(())
+            }
+        }
+
+
+        impl TryFrom<InstructionId> for Instruction {
+            type Error = ();
+
+            fn try_from(value: InstructionId) -> Result<Self, Self::Error> {
+                match value {
+                    $($( InstructionId::$name => isa!(@unit $name $( ( $( $field ),* ) )?), )*)*
+                }
+            }
+        }
+
+
+        impl Instruction {
+            pub fn to_id(self) -> InstructionId {
+                match self {
+                    $($( Self::$name { .. } => InstructionId::$name, )*)*
+                }
+            }
+
+            pub fn encode(self) -> Vec<u8> {
+                let mut bytes = vec![self.to_id().code()];
+                match self {
+                    $($( Self::$name $( ( $( $field ),* ) )? => { $($( $field.encode_operand(&mut bytes); )*)? }, )*)*
+                };
+                bytes
+            }
+
+            pub(super) fn decode_operands(id: InstructionId, bytes: &mut impl Iterator<Item = u8>) -> Result<Self, InstructionDecodingError> {
+                Ok(match id {
+                    $($( InstructionId::$name => Self::$name $( ( $( <operand_ty!($kind)>::decode_operand(bytes)? ),* ) )?, )*)*
+                })
+            }
+        }
+
+
+        impl Display for Instruction {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.to_id())?;
+                match self {
+                    $($( Self::$name $( ( $( $field ),* ) )? => { $($( write!(f, " ")?; $field.fmt_operand(f)?; )*)? }, )*)*
+                };
+                Ok(())
+            }
+        }
+    };
+    (@unit $name:ident) => { Ok(Self::$name) };
+    (@unit $name:ident ( $( $field:ident ),* )) => { Err(()) };
+}
+
+
+macro_rules! operand_ty {
+    (Register) => { Register };
+    (Number) => { u16 };
+}
+
+
+isa! {
+    serv 0b_0000 "" {
+        0b_0000 "skip" Skip "does nothing";
+        0b_0001 "wait" Wait "while $oa: $oa -= 1";
+        0b_0010 "pause" Pause "pauses cpu until *signal* (note: currently within emulator implemented same as stop) (another note: i dont know *what* is signal)";
+        0b_0011 "stop" Stop "completely stops cpu";
+    }
+    regs 0b_0001 "" {
+        0b_0000 "set" Set(reg: Register, val: Number) "$#1 = #2";
+        0b_0010 "setnz" SetIfNotZero(reg: Register, val: Number) "if $oc: $#1 = #2";
+        0b_0011 "setz" SetIfZero(reg: Register, val: Number) "if !$oc: $#1 = #2";
+        0b_0100 "copy" Copy(a: Register, b: Register) "$#2 = $#1";
+        0b_0101 "swap" Swap(a: Register, b: Register) "($#2, $#1) = ($#1, $#2)";
+    }
+    mem 0b_0010 "little endian, w for word, b for byte" {
+        0b_0000 "writeb" WriteByte "*$oc = $oa (writes only lower 8-bits)";
+        0b_0001 "writew" WriteWord "*$oc = $oa";
+        0b_0010 "readb" ReadByte "$oa = *$oc (overwrites only lower 8-bits)";
+        0b_0011 "readw" ReadWord "$oa = *$oc";
+    }
+    alu 0b_0011 "" {
+        0b_0000 "add" Add "$oc = $oa + $ob  / when overflows first bit of $ss is set, otherwise cleared";
+        0b_0010 "cmp" CompareUnsigned "$oc = (($oa >= $ob) << 1) | ($oa == $ob)  / unsigned";
+        0b_0011 "cmps" CompareSigned "$oc = (($oa >= $ob) << 1) | ($oa == $ob)  / signed";
+        0b_0100 "and" And "$oc = $oa & $ob";
+        0b_0101 "or" Or "$oc = $oa | $ob";
+        0b_0110 "xor" Xor "$oc = $oa ^ $ob";
+        0b_0111 "rot" Rotate "$oa <<<= 1";
+    }
+    bus 0b_0100 "" {
+        0b_0000 "iow" IoWrite "$oc <- $oa";
+        0b_0001 "ior" IoRead "$oc -> $oa";
+        0b_0010 "ioww" IoWaitForWrite "wait for message to be sent (timeout in ticks can be set with $oa, when 0xffff it is disabled)";
+        0b_0011 "iowr" IoWaitForRead "wait for $oc to send message to cpu (if $oc == 0x00, waits for any device) (timeout in ticks can be set with $oa, when 0xffff it is disabled)";
+        0b_0100 "iocw" IoBufClearWrite "clears buffer for sending messages";
+        0b_0101 "iocr" IoBufClearRead "clears buffer for receiving messages";
+        0b_0111 "iorw" IoBufReadWrite "reads write buffer (if empty $oc = 0x00)";
+    }
+}
+
+
+pub struct Group {
+    pub name: &'static str,
+    pub code: u8,
+    pub note: &'static str,
+    pub instructions: &'static [InstructionSpec],
+}
+
+
+pub struct InstructionSpec {
+    pub id: InstructionId,
+    pub mnemonic: &'static str,
+    pub code: u8,
+    pub arguments: &'static [Argument],
+    pub summary: &'static str,
+}
+
+
+trait Operand: Sized {
+    fn encode_operand(self, bytes: &mut Vec<u8>);
+
+    fn decode_operand(bytes: &mut impl Iterator<Item = u8>) -> Result<Self, InstructionDecodingError>;
+
+    fn fmt_operand(&self, f: &mut Formatter<'_>) -> std::fmt::Result;
+}
+
+
+impl Operand for Register {
+    fn encode_operand(self, bytes: &mut Vec<u8>) {
+        bytes.push(self.to_addr());
+    }
+
+    fn decode_operand(bytes: &mut impl Iterator<Item = u8>) -> Result<Self, InstructionDecodingError> {
+        let addr = bytes.next().ok_or(InstructionDecodingError::EarlyEOB)?;
+        Register::from_addr(addr).ok_or(InstructionDecodingError::InvalidRegister)
+    }
+
+    fn fmt_operand(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+
+impl Operand for u16 {
+    fn encode_operand(self, bytes: &mut Vec<u8>) {
+        bytes.extend(self.to_le_bytes());
+    }
+
+    fn decode_operand(bytes: &mut impl Iterator<Item = u8>) -> Result<Self, InstructionDecodingError> {
+        let mut next_byte = || bytes.next().ok_or(InstructionDecodingError::EarlyEOB);
+        Ok(u16::from_le_bytes([next_byte()?, next_byte()?]))
+    }
+
+    fn fmt_operand(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#d{self}")
+    }
+}
+
+
+pub struct IsaReference;
+
+
+impl Display for IsaReference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "regs (16-bit):")?;
+        let mut last_group = None;
+        for reg in Register::ALL {
+            let addr = reg.to_addr();
+            if last_group.is_some_and(|group| group != addr >> 4) {
+                writeln!(f)?;
+            };
+            last_group = Some(addr >> 4);
+
+            writeln!(f, "    {reg}  {:0>4b} {:0>4b}  - {}", addr >> 4, addr & 0xf, reg.description())?;
+        };
+
+        writeln!(f)?;
+        writeln!(f)?;
+        writeln!(f, "instr (encoding: <group> <instr>, ie: `add` is 0b0011_0000):")?;
+        for group in ISA {
+            if group.note.is_empty() {
+                writeln!(f, "- {} {:0>4b}:", group.name, group.code)?;
+            } else {
+                writeln!(f, "- {} {:0>4b} ({}):", group.name, group.code, group.note)?;
+            };
+
+            for instr in group.instructions {
+                let args = (1..=instr.arguments.len()).map(|i| format!(" #{i}")).collect::<String>();
+                writeln!(f, "    {:0>4b} {:<11} ; {}", instr.code & 0xf, format!("{}{args}", instr.mnemonic), instr.summary)?;
+            };
+        };
+
+        Ok(())
+    }
+}
@@ -1,5 +1,13 @@
+#![feature(let_chains)]
+
 mod instruction;
 mod register;
+mod executable;
+mod object;
+mod disassembler;
 
-pub use instruction::{Instruction, InstructionId, Argument};
+pub use instruction::{Instruction, InstructionId, InstructionDecodingError, Argument, Group, InstructionSpec, IsaReference, ISA};
 pub use register::Register;
+pub use disassembler::{Disassembled, DisassembledSegment, Disassembler, Disassembly};
+pub use executable::{Executable, ExecutableDecodingError, Segment, Symbol, ISA_VERSION};
+pub use object::{Object, ObjectDecodingError, Relocation, RelocationKind, RelocationTarget};
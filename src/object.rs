@@ -0,0 +1,282 @@
+use std::fmt::{Display, Formatter};
+use crate::executable::{decode_symbols, encode_name, encode_symbols, EarlyEOB, Reader};
+use crate::{Symbol, ISA_VERSION};
+
+
+// assembled code whose addresses are relative to where it gets placed, left for a linker to fix up
+//
+// layout (all integers are little endian):
+//   magic    4 bytes  "WTTO"
+//   isa      u16
+//   count    u16      amount of sections
+//   section  * count
+//     kind   u8
+//     len    u32
+//     data   len bytes
+//
+// symbols (exports and all labels) are stored as in an executable with addresses relative to the code,
+// imports are names (u16 length + bytes), relocations are `offset u16, kind u8, target u8, import u16, addend u16`
+#[derive(Debug, Clone)]
+pub struct Object {
+    pub isa_version: u16,
+    pub code: Vec<u8>,
+    pub exports: Vec<Symbol>,
+    pub imports: Vec<String>,
+    pub relocations: Vec<Relocation>,
+    pub symbols: Option<Vec<Symbol>>,
+}
+
+
+// a word within the code which depends on where things end up
+#[derive(Debug, Clone)]
+pub struct Relocation {
+    pub offset: u16,
+    pub kind: RelocationKind,
+    pub target: RelocationTarget,
+    pub addend: u16,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelocationKind {
+    Abs16,  // the whole address
+    Hi,  // its high byte
+    Lo,  // its low byte
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelocationTarget {
+    Code,  // start of the code of the object itself
+    Import(u16),  // index into imports
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SectionKind {
+    Code,
+    Exports,
+    Imports,
+    Relocations,
+    Symbols,
+}
+
+
+impl SectionKind {
+    fn code(self) -> u8 {
+        match self {
+            Self::Code => 0x01,
+            Self::Exports => 0x02,
+            Self::Imports => 0x03,
+            Self::Relocations => 0x04,
+            Self::Symbols => 0x05,
+        }
+    }
+}
+
+
+impl TryFrom<u8> for SectionKind {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::Code),
+            0x02 => Ok(Self::Exports),
+            0x03 => Ok(Self::Imports),
+            0x04 => Ok(Self::Relocations),
+            0x05 => Ok(Self::Symbols),
+            _ => Err(()),
+        }
+    }
+}
+
+
+impl RelocationKind {
+    // word written for an address
+    pub fn apply(self, addr: u16) -> u16 {
+        match self {
+            Self::Abs16 => addr,
+            Self::Hi => addr >> 8,
+            Self::Lo => addr & 0x00ff,
+        }
+    }
+
+    fn code(self) -> u8 {
+        match self {
+            Self::Abs16 => 0x01,
+            Self::Hi => 0x02,
+            Self::Lo => 0x03,
+        }
+    }
+}
+
+
+impl TryFrom<u8> for RelocationKind {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::Abs16),
+            0x02 => Ok(Self::Hi),
+            0x03 => Ok(Self::Lo),
+            _ => Err(()),
+        }
+    }
+}
+
+
+impl Display for RelocationKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Abs16 => write!(f, "abs16"),
+            Self::Hi => write!(f, "hi"),
+            Self::Lo => write!(f, "lo"),
+        }
+    }
+}
+
+
+impl Object {
+    pub const MAGIC: [u8; 4] = *b"WTTO";
+
+    pub fn new(code: Vec<u8>) -> Self {
+        Self { isa_version: ISA_VERSION, code, exports: Vec::new(), imports: Vec::new(), relocations: Vec::new(), symbols: None }
+    }
+
+    pub fn is_object(bytes: &[u8]) -> bool {
+        bytes.starts_with(&Self::MAGIC)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(Self::MAGIC);
+        bytes.extend(self.isa_version.to_le_bytes());
+
+        let count = 4 + self.symbols.is_some() as usize;
+        bytes.extend((count as u16).to_le_bytes());
+
+        let mut push_section = |kind: SectionKind, data: &[u8]| {
+            bytes.push(kind.code());
+            bytes.extend((data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(data);
+        };
+
+        push_section(SectionKind::Code, &self.code);
+        push_section(SectionKind::Exports, &encode_symbols(&self.exports));
+        push_section(SectionKind::Imports, &self.imports.iter().flat_map(|name| encode_name(name)).collect::<Vec<_>>());
+
+        let mut data = Vec::new();
+        for reloc in self.relocations.iter() {
+            let (target, import) = match reloc.target {
+                RelocationTarget::Code => (0x00, 0),
+                RelocationTarget::Import(i) => (0x01, i),
+            };
+            data.extend(reloc.offset.to_le_bytes());
+            data.push(reloc.kind.code());
+            data.push(target);
+            data.extend(import.to_le_bytes());
+            data.extend(reloc.addend.to_le_bytes());
+        };
+        push_section(SectionKind::Relocations, &data);
+
+        if let Some(symbols) = &self.symbols {
+            push_section(SectionKind::Symbols, &encode_symbols(symbols));
+        };
+
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ObjectDecodingError> {
+        let mut reader = Reader::new(bytes);
+
+        if reader.take(4)? != Self::MAGIC {
+            return Err(ObjectDecodingError::InvalidMagic);
+        };
+
+        let isa_version = reader.u16()?;
+        let count = reader.u16()?;
+
+        let mut obj = Self { isa_version, ..Self::new(Vec::new()) };
+        for _ in 0..count {
+            let kind = SectionKind::try_from(reader.u8()?).map_err(|()| ObjectDecodingError::UnknownSection)?;
+            let len = reader.u32()? as usize;
+            let data = reader.take(len)?;
+
+            match kind {
+                SectionKind::Code => { obj.code = data.to_vec(); },
+                SectionKind::Exports => { obj.exports = decode_symbols(data).ok_or(ObjectDecodingError::InvalidSymbol)?; },
+                SectionKind::Imports => {
+                    let mut names = Reader::new(data);
+                    while !names.is_empty() {
+                        obj.imports.push(names.name()?.ok_or(ObjectDecodingError::InvalidSymbol)?);
+                    };
+                },
+                SectionKind::Relocations => {
+                    let mut relocs = Reader::new(data);
+                    while !relocs.is_empty() {
+                        let offset = relocs.u16()?;
+                        let kind = RelocationKind::try_from(relocs.u8()?).map_err(|()| ObjectDecodingError::InvalidRelocation { offset })?;
+                        let target = relocs.u8()?;
+                        let import = relocs.u16()?;
+                        let addend = relocs.u16()?;
+
+                        let target = match target {
+                            0x00 => RelocationTarget::Code,
+                            0x01 => RelocationTarget::Import(import),
+                            _ => { return Err(ObjectDecodingError::InvalidRelocation { offset }); },
+                        };
+                        obj.relocations.push(Relocation { offset, kind, target, addend });
+                    };
+                },
+                SectionKind::Symbols => { obj.symbols = Some(decode_symbols(data).ok_or(ObjectDecodingError::InvalidSymbol)?); },
+            };
+        };
+
+        // a relocation has to patch a whole word of the code and refer to an existing import
+        for reloc in obj.relocations.iter() {
+            let in_code = reloc.offset as usize + 2 <= obj.code.len();
+            let known = match reloc.target {
+                RelocationTarget::Code => true,
+                RelocationTarget::Import(i) => (i as usize) < obj.imports.len(),
+            };
+            if !in_code || !known {
+                return Err(ObjectDecodingError::InvalidRelocation { offset: reloc.offset });
+            };
+        };
+
+        Ok(obj)
+    }
+
+    pub fn export(&self, name: &str) -> Option<u16> {
+        self.exports.iter().find(|sym| sym.name == name).map(|sym| sym.addr)
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
+pub enum ObjectDecodingError {
+    InvalidMagic,
+    EarlyEOB,
+    UnknownSection,
+    InvalidSymbol,
+    InvalidRelocation { offset: u16 },
+}
+
+impl Display for ObjectDecodingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidMagic => write!(f, "not a watto object (invalid magic)"),
+            Self::EarlyEOB => write!(f, "too early end of byte stream"),
+            Self::UnknownSection => write!(f, "encountered a section of unknown kind"),
+            Self::InvalidSymbol => write!(f, "symbol name is not valid utf-8"),
+            Self::InvalidRelocation { offset } => write!(f, "relocation at 0x{offset:0>4x} is invalid"),
+        }
+    }
+}
+
+impl std::error::Error for ObjectDecodingError {}
+
+impl From<EarlyEOB> for ObjectDecodingError {
+    fn from(_: EarlyEOB) -> Self {
+        Self::EarlyEOB
+    }
+}
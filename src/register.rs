@@ -21,6 +21,13 @@ pub enum Register {
 
 
 impl Register {
+    pub const ALL: [Register; 11] = [
+        Self::ServiceInstruction, Self::ServiceStatus,
+        Self::OperandA, Self::OperandB, Self::OperandC,
+        Self::GeneralA, Self::GeneralB, Self::GeneralC, Self::GeneralD,
+        Self::DisplayA, Self::DisplayB,
+    ];
+    
     pub fn to_addr(self) -> u8 {
         match self {
             Self::ServiceInstruction => 0b_0000_0000,
@@ -61,6 +68,25 @@ impl Register {
         }
     }
 
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::ServiceInstruction => "service instruction",
+            Self::ServiceStatus => "service status",
+
+            Self::OperandA => "operand a",
+            Self::OperandB => "operand b",
+            Self::OperandC => "operand c",
+
+            Self::GeneralA => "general a",
+            Self::GeneralB => "general b",
+            Self::GeneralC => "general c",
+            Self::GeneralD => "general d",
+
+            Self::DisplayA => "display a",
+            Self::DisplayB => "display b",
+        }
+    }
+
     pub fn to_index(self) -> usize {
         match self {
             Self::ServiceInstruction => 0,
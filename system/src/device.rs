@@ -43,4 +43,8 @@ impl Device {
             self.bus_msg_send = Some(msg);
         };
     }
+    
+    pub fn is_idle(&self) -> bool {
+        self.bus_msg_send.is_none() && self.bus_msg_rcv.is_none() && self.kernel.is_idle()
+    }
 }
@@ -0,0 +1,145 @@
+use std::fmt::{Display, Formatter};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::kernels::Kernel;
+
+// requests (sent as a message to the clock, answered with one byte sent back):
+// 0x00..=0x06: seconds, minutes, hours, day, month, year (low, high byte), utc
+// 0x10..=0x17: ticks of the clock since start, little endian
+// asking for the first byte of either takes a snapshot the rest is read from, so reads are consistent
+const TIME_FIELDS: u8 = 0x00;
+const TICK_FIELDS: u8 = 0x10;
+
+
+// what time the clock tells
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClockMode {
+    // host's wall clock
+    Host,
+    // always the same (unix) time
+    Fixed(u64),
+    // starts at a (unix) time and advances with clock's own ticks, given how many there are per second
+    Virtual { start: u64, freq: u32 },
+}
+
+
+#[derive(Debug, Clone)]
+pub struct RealtimeClock {
+    mode: ClockMode,
+    ticks: u64,
+    time: [u8; 7],
+    tick_snapshot: u64,
+    bus_rcv_buf: Option<(u8, u8)>,
+    reply: Option<(u8, u8)>,
+    sending: bool,
+}
+
+
+impl RealtimeClock {
+    pub fn new(mode: ClockMode) -> Self {
+        Self { mode, ticks: 0, time: [0; 7], tick_snapshot: 0, bus_rcv_buf: None, reply: None, sending: false }
+    }
+
+    // seconds since unix epoch
+    pub fn now(&self) -> u64 {
+        match self.mode {
+            ClockMode::Host => SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |dur| dur.as_secs()),
+            ClockMode::Fixed(time) => time,
+            ClockMode::Virtual { start, freq } => start + self.ticks / freq as u64,
+        }
+    }
+
+    fn answer(&mut self, req: u8) -> Option<u8> {
+        match req {
+            TIME_FIELDS..=0x06 => {
+                if req == TIME_FIELDS {
+                    self.time = civil(self.now());
+                };
+                Some(self.time[(req - TIME_FIELDS) as usize])
+            },
+            TICK_FIELDS..=0x17 => {
+                if req == TICK_FIELDS {
+                    self.tick_snapshot = self.ticks;
+                };
+                Some(self.tick_snapshot.to_le_bytes()[(req - TICK_FIELDS) as usize])
+            },
+            _ => None,
+        }
+    }
+}
+
+
+// seconds, minutes, hours, day, month and year (as two bytes) of a unix time
+fn civil(time: u64) -> [u8; 7] {
+    let (days, secs) = (time / 86400, time % 86400);
+
+    // days since 1970-01-01 to a date, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = (yoe + era * 400 + (month <= 2) as u64) as u16;
+
+    let [year_lo, year_hi] = year.to_le_bytes();
+    [(secs % 60) as u8, (secs / 60 % 60) as u8, (secs / 3600) as u8, day as u8, month as u8, year_lo, year_hi]
+}
+
+
+impl Display for RealtimeClock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let [sec, min, hour, day, month, year_lo, year_hi] = civil(self.now());
+        write!(f, "time: {:0>4}-{month:0>2}-{day:0>2} {hour:0>2}:{min:0>2}:{sec:0>2} | ticks: {}", u16::from_le_bytes([year_lo, year_hi]), self.ticks)
+    }
+}
+
+
+impl Kernel for RealtimeClock {
+    fn name(&self) -> &'static str {
+        "clock"
+    }
+
+    fn init_bus(&mut self, _addr: u8) {
+        //
+    }
+
+    fn send_bus_msg(&mut self) -> Option<(u8, u8)> {
+        if self.sending {
+            return None;
+        };
+
+        let reply = self.reply.take()?;
+        self.sending = true;
+        Some(reply)
+    }
+
+    fn end_send_bus_msg(&mut self) {
+        self.sending = false;
+    }
+
+    fn rcv_bus_msg(&mut self, msg: (u8, u8)) {
+        assert!(self.bus_rcv_buf.is_none());
+        self.bus_rcv_buf = Some(msg);
+    }
+
+    // one request at a time, next one only once the answer is on its way
+    fn can_rcv_bus_msg(&self) -> bool {
+        self.bus_rcv_buf.is_none() && self.reply.is_none() && !self.sending
+    }
+
+    fn is_idle(&self) -> bool {
+        self.bus_rcv_buf.is_none() && self.reply.is_none()
+    }
+
+    fn tick(&mut self) {
+        self.ticks = self.ticks.wrapping_add(1);
+
+        // unknown requests are ignored
+        if let Some((req, from)) = self.bus_rcv_buf.take()
+            && let Some(b) = self.answer(req) {
+            self.reply = Some((b, from));
+        };
+    }
+}
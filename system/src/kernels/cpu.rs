@@ -1,13 +1,10 @@
+use std::collections::BTreeSet;
+use std::error::Error;
 use std::fmt::{Display, Formatter};
-use watto::{Instruction, reg, Register};
-use super::Kernel;
+use watto::{Executable, Instruction, InstructionDecodingError, reg, Register};
+use super::{Halt, HaltReason, Kernel};
 
 
-enum HaltState {
-    Paused,
-    Stopped,
-}
-
 pub struct Cpu {
     mem: Vec<u8>,
     regs: [u16; 11],
@@ -16,8 +13,14 @@ pub struct Cpu {
     bus_buf_send_end: bool,
     bus_buf_rcv: Option<(u8, u8)>,
     bus_rcv_waiting: bool,
-    halt: Option<HaltState>,
+    halt: Option<HaltReason>,
     last_instr: Option<Instruction>,
+    exit_reg: Option<Register>,
+    fault_policy: FaultPolicy,
+    breakpoints: BTreeSet<u16>,
+    break_at: Option<u16>,
+    resume_at: Option<u16>,
+    executed: u64,
 }
 
 impl Cpu {
@@ -37,15 +40,465 @@ impl Cpu {
             bus_rcv_waiting: false,
             halt: None,
             last_instr: None,
+            exit_reg: None,
+            fault_policy: FaultPolicy::Halt,
+            breakpoints: BTreeSet::new(),
+            break_at: None,
+            resume_at: None,
+            executed: 0,
         }
     }
     
+    // register whose value is reported as the exit code once cpu halts
+    pub fn with_exit_reg(mut self, reg: Option<Register>) -> Self {
+        self.exit_reg = reg;
+        self
+    }
+    
+    pub fn with_fault_policy(mut self, policy: FaultPolicy) -> Self {
+        self.fault_policy = policy;
+        self
+    }
+    
+    pub fn from_executable(ram_size: u16, exe: &Executable) -> Result<Self, LoadingError> {
+        if exe.isa_version != watto::ISA_VERSION {
+            return Err(LoadingError::IsaMismatch { expected: watto::ISA_VERSION, found: exe.isa_version });
+        };
+
+        let mut cpu = Self::new(ram_size, &[]);
+
+        for seg in exe.segments.iter() {
+            if seg.end() > ram_size as usize {
+                return Err(LoadingError::SegmentOutOfRam { addr: seg.addr, size: seg.size });
+            };
+
+            cpu.mem[seg.addr as usize..][..seg.data.len()].copy_from_slice(&seg.data);
+        };
+
+        cpu.regs[reg!(si)] = exe.entry;
+
+        Ok(cpu)
+    }
+
+    pub fn reg(&self, reg: Register) -> u16 {
+        self.regs[reg.to_index()]
+    }
+    
+    pub fn set_reg(&mut self, reg: Register, val: u16) {
+        self.regs[reg.to_index()] = val;
+    }
+    
+    pub fn mem(&self) -> &[u8] {
+        &self.mem
+    }
+    
+    pub fn mem_mut(&mut self) -> &mut [u8] {
+        &mut self.mem
+    }
+    
+    // amount of instructions executed (or faulted on) so far, a `wait` counts once per tick
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+    
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+    
+    pub fn add_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.insert(addr)
+    }
+    
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+    
+    // address of the breakpoint cpu is currently paused at
+    pub fn breakpoint(&self) -> Option<u16> {
+        self.break_at
+    }
+    
+    // leaves a breakpoint, the instruction at it is executed on the next tick
+    pub fn resume(&mut self) {
+        self.break_at = None;
+        self.resume_at = Some(self.regs[reg!(si)]);
+    }
+    
+    pub fn current_instruction(&self) -> Result<Instruction, CpuFault> {
+        self.instruction_at(self.regs[reg!(si)])
+    }
+    
+    pub fn instruction_at(&self, addr: u16) -> Result<Instruction, CpuFault> {
+        let si = addr as usize;
+        
+        let decoded = if let FaultPolicy::Wrap = self.fault_policy {
+            Instruction::decode_from_iter(&mut self.mem.iter().cycle().skip(si % self.mem.len()).copied())
+        } else if si >= self.mem.len() {
+            return Err(CpuFault::FetchPastRam);
+        } else {
+            Instruction::decode_from_iter(&mut self.mem[si..].iter().copied())
+        };
+        
+        decoded.map_err(|err| match err {
+            InstructionDecodingError::EarlyEOB => CpuFault::FetchPastRam,
+            InstructionDecodingError::InvalidId => CpuFault::InvalidOpcode,
+            InstructionDecodingError::InvalidRegister => CpuFault::InvalidRegister,
+        })
+    }
+    
+    fn raise(&mut self, kind: CpuFault) {
+        let at = self.regs[reg!(si)];
+        let bytes = self.mem.iter().skip(at as usize).take(4).copied().collect();
+        let fault = Fault { kind, at, bytes };
+        
+        match self.fault_policy {
+            FaultPolicy::Interrupt { vector } if at != vector => {
+                self.regs[reg!(ss)] = (self.regs[reg!(ss)] & 0x00ff) | ((kind.code() as u16) << 8);
+                self.regs[reg!(gc)] = at;
+                self.regs[reg!(si)] = vector;
+            },
+            _ => { self.halt = Some(HaltReason::Fault(fault)); },
+        };
+    }
+    
+    fn mem_index(&self, addr: u16) -> Result<usize, CpuFault> {
+        if (addr as usize) < self.mem.len() {
+            Ok(addr as usize)
+        } else if let FaultPolicy::Wrap = self.fault_policy {
+            Ok(addr as usize % self.mem.len())
+        } else {
+            Err(CpuFault::MemoryOutOfBounds { addr })
+        }
+    }
+    
+    // index of the second byte of a word
+    fn mem_index_hi(&self, addr: u16) -> Result<usize, CpuFault> {
+        let lo = self.mem_index(addr)?;
+        if lo + 1 < self.mem.len() {
+            Ok(lo + 1)
+        } else if let FaultPolicy::Wrap = self.fault_policy {
+            Ok(0)
+        } else {
+            Err(CpuFault::UnalignedWord { addr })
+        }
+    }
+    
+    fn read_byte(&self, addr: u16) -> Result<u8, CpuFault> {
+        Ok(self.mem[self.mem_index(addr)?])
+    }
+    
+    fn read_word(&self, addr: u16) -> Result<u16, CpuFault> {
+        Ok(u16::from_le_bytes([self.mem[self.mem_index(addr)?], self.mem[self.mem_index_hi(addr)?]]))
+    }
+    
+    fn write_byte(&mut self, addr: u16, val: u8) -> Result<(), CpuFault> {
+        let i = self.mem_index(addr)?;
+        self.mem[i] = val;
+        Ok(())
+    }
+    
+    fn write_word(&mut self, addr: u16, val: u16) -> Result<(), CpuFault> {
+        let (lo, hi) = (self.mem_index(addr)?, self.mem_index_hi(addr)?);
+        [self.mem[lo], self.mem[hi]] = val.to_le_bytes();
+        Ok(())
+    }
+    
     fn advance_si(&mut self, cur: Instruction) {
-        // todo handle overflow
+        // overflowing past 0xffff is caught as fetch past ram on the next tick
         self.regs[reg!(si)] = self.regs[reg!(si)].wrapping_add(cur.to_id().size() as u16);
     }
+
+    fn execute(&mut self, instr: Instruction) -> Result<(), CpuFault> {
+        match instr {
+            Instruction::Skip => {},
+            Instruction::Pause => { self.halt = Some(HaltReason::Paused); },
+            Instruction::Stop => { self.halt = Some(HaltReason::Stopped); },
+            Instruction::Wait => {
+                let reg_oa = &mut self.regs[reg!(oa)];
+                if *reg_oa != 0 {
+                    *reg_oa -= 1;
+                } else {
+                    self.advance_si(instr);
+                };
+            },
+
+            Instruction::Set(reg, val) => {
+                self.regs[reg.to_index()] = val;
+                
+                if reg != Register::ServiceInstruction {
+                    self.advance_si(instr);
+                };
+            },
+            Instruction::SetIfNotZero(reg, val) => {
+                if self.regs[reg!(oc)] != 0 {
+                    self.regs[reg.to_index()] = val;
+                    
+                    if reg != Register::ServiceInstruction {
+                        self.advance_si(instr);
+                    };
+                } else {
+                    self.advance_si(instr);
+                };
+            },
+            Instruction::SetIfZero(reg, val) => {
+                if self.regs[reg!(oc)] == 0 {
+                    self.regs[reg.to_index()] = val;
+                    
+                    if reg != Register::ServiceInstruction {
+                        self.advance_si(instr);
+                    };
+                } else {
+                    self.advance_si(instr);
+                };
+            },
+            Instruction::Copy(a, b) => {
+                self.regs[b.to_index()] = self.regs[a.to_index()];
+                
+                if b != Register::ServiceInstruction {
+                    self.advance_si(instr);
+                };
+            },
+            Instruction::Swap(a, b) => {
+                (self.regs[a.to_index()], self.regs[b.to_index()])
+                    = (self.regs[b.to_index()], self.regs[a.to_index()]);
+                
+                if !((a == Register::ServiceInstruction) ^ (b == Register::ServiceInstruction)) {
+                    self.advance_si(instr);
+                };
+            },
+
+            Instruction::WriteByte => {
+                let addr = self.regs[reg!(oc)];
+                let val = self.regs[reg!(oa)] as u8;
+
+                self.write_byte(addr, val)?;
+
+                self.advance_si(instr);
+            }
+            Instruction::WriteWord => {
+                let addr = self.regs[reg!(oc)];
+                let val = self.regs[reg!(oa)];
+
+                self.write_word(addr, val)?;
+
+                self.advance_si(instr);
+            }
+            Instruction::ReadByte => {
+                let addr = self.regs[reg!(oc)];
+                let val = self.read_byte(addr)?;
+                self.regs[reg!(oa)] &= 0xff00;
+                self.regs[reg!(oa)] |= val as u16;
+                self.advance_si(instr);
+            }
+            Instruction::ReadWord => {
+                let addr = self.regs[reg!(oc)];
+                self.regs[reg!(oa)] = self.read_word(addr)?;
+                self.advance_si(instr);
+            }
+
+            Instruction::Add => {
+                let sum = self.regs[reg!(oa)].overflowing_add(self.regs[reg!(ob)]);
+                self.regs[reg!(oc)] = sum.0;
+                if sum.1 {
+                    self.regs[reg!(ss)] &= 0b1111111111111110;
+                } else {
+                    self.regs[reg!(ss)] |= 0b0000000000000001;
+                };
+                self.advance_si(instr);
+            },
+            Instruction::CompareUnsigned => {
+                let reg_oa = self.regs[reg!(oa)];
+                let reg_ob = self.regs[reg!(ob)];
+
+                let are_eq = (reg_oa == reg_ob) as u16;
+                let is_lg = (reg_oa > reg_ob) as u16;
+
+                self.regs[reg!(oc)] = are_eq | (is_lg << 1);
+                self.advance_si(instr);
+            },
+            Instruction::CompareSigned => {
+                let reg_oa = self.regs[reg!(oa)] as i16;
+                let reg_ob = self.regs[reg!(ob)] as i16;
+
+                let are_eq = (reg_oa == reg_ob) as u16;
+                let is_lg = (reg_oa > reg_ob) as u16;
+
+                self.regs[reg!(oc)] = are_eq | (is_lg << 1);
+                self.advance_si(instr);
+            },
+            Instruction::And => {
+                self.regs[reg!(oc)] = self.regs[reg!(oa)] & self.regs[reg!(ob)];
+                self.advance_si(instr);
+            },
+            Instruction::Or => {
+                self.regs[reg!(oc)] = self.regs[reg!(oa)] | self.regs[reg!(ob)];
+                self.advance_si(instr);
+            },
+            Instruction::Xor => {
+                self.regs[reg!(oc)] = self.regs[reg!(oa)] ^ self.regs[reg!(ob)];
+                self.advance_si(instr);
+            },
+            Instruction::Rotate => {
+                self.regs[reg!(oa)] = self.regs[reg!(oa)].rotate_left(1);
+                self.advance_si(instr);
+            }
+
+            Instruction::IoWrite => {
+                let addr = self.regs[reg!(oc)] as u8;
+
+                if addr != 0 {
+                    let msg = self.regs[reg!(oa)].to_le_bytes()[0];
+
+                    self.bus_buf_send_end = false;
+                    self.bus_buf_send = Some((msg, addr));
+                };
+
+                self.advance_si(instr);
+            },
+            Instruction::IoRead => {
+                if let Some((msg, addr)) = self.bus_buf_rcv
+                    && (self.regs[reg!(oc)] as u8 == 0 || addr == self.regs[reg!(oc)] as u8) {
+                    self.regs[reg!(oa)] &= 0xFF00;
+                    self.regs[reg!(oa)] |= msg as u16;
+                } else {
+                    self.regs[reg!(oc)] = 0x0000;
+                };
+
+                self.advance_si(instr);
+            },
+            Instruction::IoWaitForWrite => {
+                if self.bus_buf_send_end || self.regs[reg!(oa)] == 0 {
+                    self.advance_si(instr);
+                } else if self.regs[reg!(oa)] != 0xFFFF {
+                    self.regs[reg!(oa)] -= 1;
+                };
+            },
+            Instruction::IoWaitForRead => {
+                self.bus_rcv_waiting = true;
+                if self.bus_buf_rcv.is_some_and(|(_, addr)| addr == self.regs[reg!(oc)] as u8)
+                    || self.regs[reg!(oa)] == 0 {
+                    self.bus_rcv_waiting = false;
+                    self.advance_si(instr);
+                } else if self.regs[reg!(oa)] != 0xFFFF {
+                    self.regs[reg!(oa)] -= 1;
+                };
+            },
+            Instruction::IoBufClearWrite => {
+                self.bus_buf_send = None;
+                self.advance_si(instr);
+            }
+            Instruction::IoBufClearRead => {
+                self.bus_buf_rcv = None;
+                self.advance_si(instr);
+            },
+            Instruction::IoBufReadWrite => {
+                if let Some((msg, addr)) = self.bus_buf_send {
+                    self.regs[reg!(oc)] = addr as u16;
+
+                    self.regs[reg!(oa)] &= 0xFF00;
+                    self.regs[reg!(oa)] |= msg as u16;
+                } else {
+                    self.regs[reg!(oc)] = 0x0000;
+                };
+            }
+        };
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CpuFault {
+    InvalidOpcode,
+    InvalidRegister,
+    MemoryOutOfBounds { addr: u16 },
+    UnalignedWord { addr: u16 },  // word access at the last byte of ram
+    FetchPastRam,
+}
+
+
+impl CpuFault {
+    // reported in the upper byte of $ss when raised as an interrupt
+    pub fn code(self) -> u8 {
+        match self {
+            Self::InvalidOpcode => 0x01,
+            Self::InvalidRegister => 0x02,
+            Self::MemoryOutOfBounds { .. } => 0x03,
+            Self::UnalignedWord { .. } => 0x04,
+            Self::FetchPastRam => 0x05,
+        }
+    }
+    
+    pub fn is_invalid_instruction(self) -> bool {
+        matches!(self, Self::InvalidOpcode | Self::InvalidRegister)
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fault {
+    pub kind: CpuFault,
+    pub at: u16,
+    pub bytes: Vec<u8>,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FaultPolicy {
+    Halt,
+    // out-of-bounds addresses wrap around ram, faults which can't be wrapped still halt
+    Wrap,
+    // jumps to the vector with faulting $si in $gc and fault code in upper byte of $ss,
+    // halts if the handler itself faults at the vector
+    Interrupt { vector: u16 },
+}
+
+
+impl Display for CpuFault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidOpcode => write!(f, "invalid opcode"),
+            Self::InvalidRegister => write!(f, "invalid register"),
+            Self::MemoryOutOfBounds { addr } => write!(f, "memory access out of bounds (0x{addr:0>4x})"),
+            Self::UnalignedWord { addr } => write!(f, "word access at the end of ram (0x{addr:0>4x})"),
+            Self::FetchPastRam => write!(f, "instruction fetch past ram"),
+        }
+    }
+}
+
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at 0x{:0>4x} [", self.kind, self.at)?;
+        for (i, b) in self.bytes.iter().enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            };
+            write!(f, "{b:0>2x}")?;
+        };
+        write!(f, "]")
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub enum LoadingError {
+    IsaMismatch { expected: u16, found: u16 },
+    SegmentOutOfRam { addr: u16, size: usize },
+}
+
+impl Display for LoadingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IsaMismatch { expected, found } => write!(f, "executable targets isa v{found}, but cpu implements v{expected}"),
+            Self::SegmentOutOfRam { addr, size } => write!(f, "segment at 0x{addr:0>4x} ({size} bytes) does not fit into ram"),
+        }
+    }
 }
 
+impl Error for LoadingError {}
+
+
 impl Display for Cpu {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let (si, oa, ob, oc, ga, gb, gc, gd, da, db) = (
@@ -94,218 +547,40 @@ impl Kernel for Cpu {
         self.bus_rcv_waiting || self.bus_buf_rcv.is_none()
     }
 
+    fn halt(&self) -> Option<Halt> {
+        Some(Halt {
+            reason: self.halt.clone()?,
+            exit_code: self.exit_reg.map(|reg| self.regs[reg.to_index()]),
+        })
+    }
+
+    fn is_idle(&self) -> bool {
+        self.bus_buf_send.is_none()
+    }
+    
+    fn breakpoint(&self) -> Option<u16> {
+        self.break_at
+    }
+
     fn tick(&mut self) {
         // todo better handle this
         if self.halt.is_some() {
             return;
         };
-
-        // todo somehow handle errors idk
-        let instr = Instruction::decode_from_iter(
-            &mut self.mem.iter()
-                .skip(self.regs[reg!(si)] as usize)
-                .copied()
-        );
-
-        match instr {
-            Err(_) => { self.halt = Some(HaltState::Stopped); },
-            Ok(instr) => {
-                match instr {
-                    Instruction::Skip => {},
-                    Instruction::Pause => { self.halt = Some(HaltState::Paused); },
-                    Instruction::Stop => { self.halt = Some(HaltState::Stopped); },
-                    Instruction::Wait => {
-                        let reg_oa = &mut self.regs[reg!(oa)];
-                        if *reg_oa != 0 {
-                            *reg_oa -= 1;
-                        } else {
-                            self.advance_si(instr);
-                        };
-                    },
-
-                    Instruction::Set(reg, val) => {
-                        self.regs[reg.to_index()] = val;
-                        
-                        if reg != Register::ServiceInstruction {
-                            self.advance_si(instr);
-                        };
-                    },
-                    Instruction::SetIfNotZero(reg, val) => {
-                        if self.regs[reg!(oc)] != 0 {
-                            self.regs[reg.to_index()] = val;
-                            
-                            if reg != Register::ServiceInstruction {
-                                self.advance_si(instr);
-                            };
-                        } else {
-                            self.advance_si(instr);
-                        };
-                    },
-                    Instruction::SetIfZero(reg, val) => {
-                        if self.regs[reg!(oc)] == 0 {
-                            self.regs[reg.to_index()] = val;
-                            
-                            if reg != Register::ServiceInstruction {
-                                self.advance_si(instr);
-                            };
-                        } else {
-                            self.advance_si(instr);
-                        };
-                    },
-                    Instruction::Copy(a, b) => {
-                        self.regs[b.to_index()] = self.regs[a.to_index()];
-                        
-                        if b != Register::ServiceInstruction {
-                            self.advance_si(instr);
-                        };
-                    },
-                    Instruction::Swap(a, b) => {
-                        (self.regs[a.to_index()], self.regs[b.to_index()])
-                            = (self.regs[b.to_index()], self.regs[a.to_index()]);
-                        
-                        if !((a == Register::ServiceInstruction) ^ (b == Register::ServiceInstruction)) {
-                            self.advance_si(instr);
-                        };
-                    },
-
-                    Instruction::WriteByte => {
-                        let addr = self.regs[reg!(oc)] as usize;
-                        let val = self.regs[reg!(oa)] as u8;
-
-                        // todo handle out-of-bounds addr
-                        self.mem[addr] = val;
-
-                        self.advance_si(instr);
-                    }
-                    Instruction::WriteWord => {
-                        let addr = self.regs[reg!(oc)] as usize;
-                        let val = self.regs[reg!(oa)];
-
-                        // todo handle out-of-bounds addr
-                        self.mem[addr..=(addr+1)].copy_from_slice(&val.to_le_bytes());
-
-                        self.advance_si(instr);
-                    }
-                    Instruction::ReadByte => {
-                        let addr = self.regs[reg!(oc)] as usize;
-                        self.regs[reg!(oa)] &= 0xff00;
-                        self.regs[reg!(oa)] |= self.mem[addr] as u16;
-                        self.advance_si(instr);
-                    }
-                    Instruction::ReadWord => {
-                        let addr = self.regs[reg!(oc)] as usize;
-                        self.regs[reg!(oa)] = u16::from_le_bytes([self.mem[addr], self.mem[addr+1]]);
-                        self.advance_si(instr);
-                    }
-
-                    Instruction::Add => {
-                        let sum = self.regs[reg!(oa)].overflowing_add(self.regs[reg!(ob)]);
-                        self.regs[reg!(oc)] = sum.0;
-                        if sum.1 {
-                            self.regs[reg!(ss)] &= 0b1111111111111110;
-                        } else {
-                            self.regs[reg!(ss)] |= 0b0000000000000001;
-                        };
-                        self.advance_si(instr);
-                    },
-                    Instruction::CompareUnsigned => {
-                        let reg_oa = self.regs[reg!(oa)];
-                        let reg_ob = self.regs[reg!(ob)];
-
-                        let are_eq = (reg_oa == reg_ob) as u16;
-                        let is_lg = (reg_oa > reg_ob) as u16;
-
-                        self.regs[reg!(oc)] = are_eq | (is_lg << 1);
-                        self.advance_si(instr);
-                    },
-                    Instruction::CompareSigned => {
-                        let reg_oa = self.regs[reg!(oa)] as i16;
-                        let reg_ob = self.regs[reg!(ob)] as i16;
-
-                        let are_eq = (reg_oa == reg_ob) as u16;
-                        let is_lg = (reg_oa > reg_ob) as u16;
-
-                        self.regs[reg!(oc)] = are_eq | (is_lg << 1);
-                        self.advance_si(instr);
-                    },
-                    Instruction::And => {
-                        self.regs[reg!(oc)] = self.regs[reg!(oa)] & self.regs[reg!(ob)];
-                        self.advance_si(instr);
-                    },
-                    Instruction::Or => {
-                        self.regs[reg!(oc)] = self.regs[reg!(oa)] | self.regs[reg!(ob)];
-                        self.advance_si(instr);
-                    },
-                    Instruction::Xor => {
-                        self.regs[reg!(oc)] = self.regs[reg!(oa)] ^ self.regs[reg!(ob)];
-                        self.advance_si(instr);
-                    },
-                    Instruction::Rotate => {
-                        self.regs[reg!(oa)] = self.regs[reg!(oa)].rotate_left(1);
-                        self.advance_si(instr);
-                    }
-
-                    Instruction::IoWrite => {
-                        let addr = self.regs[reg!(oc)] as u8;
-
-                        if addr != 0 {
-                            let msg = self.regs[reg!(oa)].to_le_bytes()[0];
-
-                            self.bus_buf_send_end = false;
-                            self.bus_buf_send = Some((msg, addr));
-                        };
-
-                        self.advance_si(instr);
-                    },
-                    Instruction::IoRead => {
-                        if let Some((msg, addr)) = self.bus_buf_rcv
-                            && (self.regs[reg!(oc)] as u8 == 0 || addr == self.regs[reg!(oc)] as u8) {
-                            self.regs[reg!(oa)] &= 0xFF00;
-                            self.regs[reg!(oa)] |= msg as u16;
-                        } else {
-                            self.regs[reg!(oc)] = 0x0000;
-                        };
-
-                        self.advance_si(instr);
-                    },
-                    Instruction::IoWaitForWrite => {
-                        if self.bus_buf_send_end || self.regs[reg!(oa)] == 0 {
-                            self.advance_si(instr);
-                        } else if self.regs[reg!(oa)] != 0xFFFF {
-                            self.regs[reg!(oa)] -= 1;
-                        };
-                    },
-                    Instruction::IoWaitForRead => {
-                        self.bus_rcv_waiting = true;
-                        if self.bus_buf_rcv.is_some_and(|(_, addr)| addr == self.regs[reg!(oc)] as u8)
-                            || self.regs[reg!(oa)] == 0 {
-                            self.bus_rcv_waiting = false;
-                            self.advance_si(instr);
-                        } else if self.regs[reg!(oa)] != 0xFFFF {
-                            self.regs[reg!(oa)] -= 1;
-                        };
-                    },
-                    Instruction::IoBufClearWrite => {
-                        self.bus_buf_send = None;
-                        self.advance_si(instr);
-                    }
-                    Instruction::IoBufClearRead => {
-                        self.bus_buf_rcv = None;
-                        self.advance_si(instr);
-                    },
-                    Instruction::IoBufReadWrite => {
-                        if let Some((msg, addr)) = self.bus_buf_send {
-                            self.regs[reg!(oc)] = addr as u16;
-
-                            self.regs[reg!(oa)] &= 0xFF00;
-                            self.regs[reg!(oa)] |= msg as u16;
-                        } else {
-                            self.regs[reg!(oc)] = 0x0000;
-                        };
-                    }
-                };
-                self.last_instr = Some(instr);
+        
+        let si = self.regs[reg!(si)];
+        if self.resume_at.take() != Some(si) && self.breakpoints.contains(&si) {
+            self.break_at = Some(si);
+            return;
+        };
+        
+        self.executed += 1;
+        match self.current_instruction() {
+            Ok(instr) => match self.execute(instr) {
+                Ok(()) => { self.last_instr = Some(instr); },
+                Err(kind) => { self.raise(kind); },
             },
+            Err(kind) => { self.raise(kind); },
         };
     }
 }
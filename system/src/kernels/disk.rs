@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use crate::kernels::Kernel;
+
+pub const SECTOR_SIZE: usize = 256;
+
+// commands, those marked with an argument take it as the following message:
+// 0x01 <lo>, 0x02 <hi>: select sector
+// 0x03: load selected sector into buffer, answers with status
+// 0x04 <offset>: set buffer pointer
+// 0x05: read byte at pointer into an answer, pointer moves on
+// 0x06 <byte>: write byte at pointer, pointer moves on
+// 0x07: store buffer into selected sector, answers with status
+// 0x08: flush stored sectors to image, answers with status
+// 0x09, 0x0a: answer with low, high byte of number of sectors
+const CMD_SECTOR_LO: u8 = 0x01;
+const CMD_SECTOR_HI: u8 = 0x02;
+const CMD_LOAD: u8 = 0x03;
+const CMD_SEEK: u8 = 0x04;
+const CMD_READ: u8 = 0x05;
+const CMD_WRITE: u8 = 0x06;
+const CMD_STORE: u8 = 0x07;
+const CMD_FLUSH: u8 = 0x08;
+const CMD_SIZE_LO: u8 = 0x09;
+const CMD_SIZE_HI: u8 = 0x0a;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum DiskStatus {
+    Ok = 0x00,
+    OutOfRange = 0x01,
+    ReadOnly = 0x02,
+    IoError = 0x03,
+}
+
+
+// what happens to stored sectors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiskMode {
+    // written to the image once flushed
+    ReadWrite,
+    // refused
+    ReadOnly,
+    // kept in memory only, the image is left as is
+    CopyOnWrite,
+}
+
+
+impl Display for DiskMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ReadWrite => write!(f, "read-write"),
+            Self::ReadOnly => write!(f, "read-only"),
+            Self::CopyOnWrite => write!(f, "copy-on-write"),
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub struct BlockDevice {
+    image: File,
+    len: u64,
+    mode: DiskMode,
+    sectors: u16,
+    stored: BTreeMap<u16, [u8; SECTOR_SIZE]>,  // sectors changed since last flush (or ever, when copy-on-write)
+    buf: [u8; SECTOR_SIZE],
+    sector: u16,
+    ptr: u8,
+    cmd: Option<u8>,
+    bus_rcv_buf: Option<(u8, u8)>,
+    reply: Option<(u8, u8)>,
+    sending: bool,
+}
+
+
+impl BlockDevice {
+    // image is taken as whole sectors, a partial last one is padded with zeros which are never written back
+    pub fn open(path: &Path, mode: DiskMode) -> io::Result<Self> {
+        let image = OpenOptions::new().read(true).write(mode == DiskMode::ReadWrite).open(path)?;
+        let len = image.metadata()?.len();
+        let sectors = len.div_ceil(SECTOR_SIZE as u64).try_into()
+            .map_err(|_| io::Error::other(format!("image has more than {} sectors", u16::MAX)))?;
+
+        Ok(Self {
+            image, len, mode, sectors,
+            stored: BTreeMap::new(),
+            buf: [0; SECTOR_SIZE],
+            sector: 0,
+            ptr: 0,
+            cmd: None,
+            bus_rcv_buf: None,
+            reply: None,
+            sending: false,
+        })
+    }
+
+    fn load(&mut self) -> io::Result<DiskStatus> {
+        if self.sector >= self.sectors {
+            return Ok(DiskStatus::OutOfRange);
+        };
+
+        if let Some(data) = self.stored.get(&self.sector) {
+            self.buf = *data;
+            return Ok(DiskStatus::Ok);
+        };
+
+        self.buf = [0; SECTOR_SIZE];
+        self.image.seek(SeekFrom::Start(self.sector as u64 * SECTOR_SIZE as u64))?;
+        let mut filled = 0;
+        while filled < SECTOR_SIZE {
+            match self.image.read(&mut self.buf[filled..])? {
+                0 => { break; },
+                n => { filled += n; },
+            };
+        };
+        Ok(DiskStatus::Ok)
+    }
+
+    fn store(&mut self) -> DiskStatus {
+        if self.mode == DiskMode::ReadOnly {
+            DiskStatus::ReadOnly
+        } else if self.sector >= self.sectors {
+            DiskStatus::OutOfRange
+        } else {
+            self.stored.insert(self.sector, self.buf);
+            DiskStatus::Ok
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<DiskStatus> {
+        if self.mode != DiskMode::ReadWrite {
+            return Ok(DiskStatus::Ok);
+        };
+
+        for (sector, data) in self.stored.iter() {
+            let offset = *sector as u64 * SECTOR_SIZE as u64;
+            let end = (self.len - offset).min(SECTOR_SIZE as u64) as usize;
+            self.image.seek(SeekFrom::Start(offset))?;
+            self.image.write_all(&data[..end])?;
+        };
+        self.image.sync_data()?;
+        self.stored.clear();
+        Ok(DiskStatus::Ok)
+    }
+
+    // answer to a command, if it has one
+    fn execute(&mut self, cmd: u8, arg: Option<u8>) -> Option<u8> {
+        let status = |res: io::Result<DiskStatus>| res.unwrap_or(DiskStatus::IoError) as u8;
+
+        match (cmd, arg) {
+            (CMD_SECTOR_LO, Some(arg)) => { self.sector = self.sector & 0xFF00 | arg as u16; },
+            (CMD_SECTOR_HI, Some(arg)) => { self.sector = self.sector & 0x00FF | (arg as u16) << 8; },
+            (CMD_LOAD, None) => { return Some(status(self.load())); },
+            (CMD_SEEK, Some(arg)) => { self.ptr = arg; },
+            (CMD_READ, None) => {
+                let b = self.buf[self.ptr as usize];
+                self.ptr = self.ptr.wrapping_add(1);
+                return Some(b);
+            },
+            (CMD_WRITE, Some(arg)) => {
+                self.buf[self.ptr as usize] = arg;
+                self.ptr = self.ptr.wrapping_add(1);
+            },
+            (CMD_STORE, None) => { return Some(self.store() as u8); },
+            (CMD_FLUSH, None) => { return Some(status(self.flush())); },
+            (CMD_SIZE_LO, None) => { return Some(self.sectors.to_le_bytes()[0]); },
+            (CMD_SIZE_HI, None) => { return Some(self.sectors.to_le_bytes()[1]); },
+            _ => {},
+        };
+        None
+    }
+}
+
+
+fn takes_arg(cmd: u8) -> bool {
+    matches!(cmd, CMD_SECTOR_LO | CMD_SECTOR_HI | CMD_SEEK | CMD_WRITE)
+}
+
+
+impl Display for BlockDevice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} | sector: {}/{} | ptr: 0x{:0>2x} | stored: {}", self.mode, self.sector, self.sectors, self.ptr, self.stored.len())
+    }
+}
+
+
+impl Kernel for BlockDevice {
+    fn name(&self) -> &'static str {
+        "disk"
+    }
+
+    fn init_bus(&mut self, _addr: u8) {
+        //
+    }
+
+    fn send_bus_msg(&mut self) -> Option<(u8, u8)> {
+        if self.sending {
+            return None;
+        };
+
+        let reply = self.reply.take()?;
+        self.sending = true;
+        Some(reply)
+    }
+
+    fn end_send_bus_msg(&mut self) {
+        self.sending = false;
+    }
+
+    fn rcv_bus_msg(&mut self, msg: (u8, u8)) {
+        assert!(self.bus_rcv_buf.is_none());
+        self.bus_rcv_buf = Some(msg);
+    }
+
+    // one command at a time, next one only once the answer is on its way
+    fn can_rcv_bus_msg(&self) -> bool {
+        self.bus_rcv_buf.is_none() && self.reply.is_none() && !self.sending
+    }
+
+    fn is_idle(&self) -> bool {
+        self.bus_rcv_buf.is_none() && self.reply.is_none()
+    }
+
+    fn tick(&mut self) {
+        let Some((msg, from)) = self.bus_rcv_buf.take() else { return };
+
+        let answer = match self.cmd.take() {
+            Some(cmd) => self.execute(cmd, Some(msg)),
+            None if takes_arg(msg) => {
+                self.cmd = Some(msg);
+                None
+            },
+            None => self.execute(msg, None),
+        };
+        self.reply = answer.map(|b| (b, from));
+    }
+}
@@ -0,0 +1,201 @@
+use std::fmt::{Display, Formatter};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use crate::kernels::Kernel;
+
+// printable ascii is written at the cursor which then moves on (wrapping at the end of a line, scrolling at the bottom),
+// other messages are commands, those marked with an argument take it as the following message:
+// 0x01 <col>, 0x02 <row>: move cursor
+// 0x03: scroll up by a line
+// 0x04: clear rest of the line
+// 0x06: move cursor home
+// 0x08: move cursor back
+// 0x0a: new line
+// 0x0c: clear screen, move cursor home
+// 0x0d: move cursor to the start of the line
+const CMD_COL: u8 = 0x01;
+const CMD_ROW: u8 = 0x02;
+const CMD_SCROLL: u8 = 0x03;
+const CMD_CLEAR_LINE: u8 = 0x04;
+const CMD_HOME: u8 = 0x06;
+const CMD_BACK: u8 = 0x08;
+const CMD_NEW_LINE: u8 = 0x0a;
+const CMD_CLEAR: u8 = 0x0c;
+const CMD_RETURN: u8 = 0x0d;
+
+
+// where the grid is shown
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisplayOutput {
+    // host terminal, redrawn with ansi escapes
+    Terminal,
+    // text file, rewritten with the whole grid on each change
+    Snapshot(PathBuf),
+}
+
+
+#[derive(Debug, Clone)]
+pub struct TextDisplay {
+    cols: u8,
+    rows: u8,
+    grid: Vec<u8>,
+    cursor: (u8, u8),
+    output: DisplayOutput,
+    cmd: Option<u8>,
+    bus_rcv_buf: Option<(u8, u8)>,
+    dirty: bool,
+    drawn: bool,
+}
+
+
+impl TextDisplay {
+    pub fn new(cols: u8, rows: u8, output: DisplayOutput) -> Self {
+        let (cols, rows) = (cols.max(1), rows.max(1));
+        Self { cols, rows, grid: vec![b' '; cols as usize * rows as usize], cursor: (0, 0), output, cmd: None, bus_rcv_buf: None, dirty: true, drawn: false }
+    }
+
+    pub fn cursor(&self) -> (u8, u8) {
+        self.cursor
+    }
+
+    pub fn line(&self, row: u8) -> &[u8] {
+        &self.grid[row as usize * self.cols as usize..][..self.cols as usize]
+    }
+
+    // every line with trailing spaces trimmed
+    pub fn text(&self) -> String {
+        (0..self.rows)
+            .map(|row| String::from_utf8_lossy(self.line(row)).trim_end().to_string() + "\n")
+            .collect()
+    }
+
+    fn scroll(&mut self) {
+        self.grid.drain(..self.cols as usize);
+        self.grid.resize(self.cols as usize * self.rows as usize, b' ');
+    }
+
+    fn new_line(&mut self) {
+        self.cursor.0 = 0;
+        if self.cursor.1 + 1 < self.rows {
+            self.cursor.1 += 1;
+        } else {
+            self.scroll();
+        };
+    }
+
+    fn put(&mut self, c: u8) {
+        let (col, row) = self.cursor;
+        self.grid[row as usize * self.cols as usize + col as usize] = c;
+        if col + 1 < self.cols {
+            self.cursor.0 += 1;
+        } else {
+            self.new_line();
+        };
+    }
+
+    fn execute(&mut self, cmd: u8, arg: Option<u8>) {
+        match (cmd, arg) {
+            (CMD_COL, Some(col)) => { self.cursor.0 = col.min(self.cols - 1); },
+            (CMD_ROW, Some(row)) => { self.cursor.1 = row.min(self.rows - 1); },
+            (CMD_SCROLL, None) => { self.scroll(); },
+            (CMD_CLEAR_LINE, None) => {
+                let (col, row) = self.cursor;
+                self.grid[row as usize * self.cols as usize..][col as usize..self.cols as usize].fill(b' ');
+            },
+            (CMD_HOME, None) => { self.cursor = (0, 0); },
+            (CMD_BACK, None) => { self.cursor.0 = self.cursor.0.saturating_sub(1); },
+            (CMD_NEW_LINE, None) => { self.new_line(); },
+            (CMD_CLEAR, None) => {
+                self.grid.fill(b' ');
+                self.cursor = (0, 0);
+            },
+            (CMD_RETURN, None) => { self.cursor.0 = 0; },
+            (0x20..=0x7e, None) => { self.put(cmd); },
+            _ => {},
+        };
+    }
+
+    fn render(&mut self) -> io::Result<()> {
+        match &self.output {
+            DisplayOutput::Terminal => {
+                let mut out = io::stdout().lock();
+                if !self.drawn {
+                    write!(out, "\x1b[2J")?;
+                };
+                write!(out, "\x1b[H")?;
+                for row in 0..self.rows {
+                    out.write_all(self.line(row))?;
+                    write!(out, "\x1b[K\r\n")?;
+                };
+                write!(out, "\x1b[{};{}H", self.cursor.1 as u16 + 1, self.cursor.0 as u16 + 1)?;
+                out.flush()?;
+            },
+            DisplayOutput::Snapshot(path) => {
+                std::fs::write(path, self.text())?;
+            },
+        };
+        self.drawn = true;
+        Ok(())
+    }
+}
+
+
+fn takes_arg(cmd: u8) -> bool {
+    matches!(cmd, CMD_COL | CMD_ROW)
+}
+
+
+impl Display for TextDisplay {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{} | cursor: {}, {} | line: {:?}", self.cols, self.rows, self.cursor.0, self.cursor.1, String::from_utf8_lossy(self.line(self.cursor.1)).trim_end())
+    }
+}
+
+
+impl Kernel for TextDisplay {
+    fn name(&self) -> &'static str {
+        "display"
+    }
+
+    fn init_bus(&mut self, _addr: u8) {
+        //
+    }
+
+    fn send_bus_msg(&mut self) -> Option<(u8, u8)> {
+        None
+    }
+
+    fn end_send_bus_msg(&mut self) {
+        //
+    }
+
+    fn rcv_bus_msg(&mut self, msg: (u8, u8)) {
+        assert!(self.bus_rcv_buf.is_none());
+        self.bus_rcv_buf = Some(msg);
+    }
+
+    fn can_rcv_bus_msg(&self) -> bool {
+        self.bus_rcv_buf.is_none()
+    }
+
+    fn is_idle(&self) -> bool {
+        self.bus_rcv_buf.is_none() && !self.dirty
+    }
+
+    fn tick(&mut self) {
+        if let Some((msg, _)) = self.bus_rcv_buf.take() {
+            match self.cmd.take() {
+                Some(cmd) => { self.execute(cmd, Some(msg)); },
+                None if takes_arg(msg) => { self.cmd = Some(msg); },
+                None => { self.execute(msg, None); },
+            };
+            self.dirty = true;
+        };
+
+        // a display which cannot be drawn to is no reason to stop the program
+        if self.dirty {
+            let _ = self.render();
+            self.dirty = false;
+        };
+    }
+}
@@ -3,10 +3,18 @@ use enum_dispatch::enum_dispatch;
 
 mod cpu;
 mod serial;
+mod clock;
+mod timer;
+mod disk;
+mod display;
 
 
-pub use cpu::Cpu;
-pub use serial::Serial;
+pub use cpu::{Cpu, CpuFault, Fault, FaultPolicy, LoadingError};
+pub use serial::{stdin_input, Overflow, Serial};
+pub use clock::{ClockMode, RealtimeClock};
+pub use timer::{Timer, TimerMode};
+pub use disk::{BlockDevice, DiskMode, DiskStatus, SECTOR_SIZE};
+pub use display::{DisplayOutput, TextDisplay};
 
 
 #[enum_dispatch(DeviceKernel)]
@@ -24,6 +32,57 @@ pub trait Kernel: Display {
     fn rcv_bus_msg(&mut self, msg: (u8, u8));
     
     fn can_rcv_bus_msg(&self) -> bool;
+    
+    fn halt(&self) -> Option<Halt> {
+        None
+    }
+    
+    // whether there is nothing left in flight (eg unsent messages or unprinted chars)
+    fn is_idle(&self) -> bool {
+        true
+    }
+    
+    // address of the breakpoint the kernel is paused at, if any
+    fn breakpoint(&self) -> Option<u16> {
+        None
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HaltReason {
+    Stopped,
+    Paused,
+    Fault(Fault),
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Halt {
+    pub reason: HaltReason,
+    pub exit_code: Option<u16>,
+}
+
+
+impl Display for HaltReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stopped => write!(f, "stopped"),
+            Self::Paused => write!(f, "paused"),
+            Self::Fault(fault) => write!(f, "{fault}"),
+        }
+    }
+}
+
+
+impl Display for Halt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason)?;
+        if let Some(code) = self.exit_code {
+            write!(f, " with exit code {code}")?;
+        };
+        Ok(())
+    }
 }
 
 
@@ -31,7 +90,11 @@ pub trait Kernel: Display {
 #[enum_dispatch]
 pub enum DeviceKernel {
     Cpu,
-    Serial
+    Serial,
+    RealtimeClock,
+    Timer,
+    BlockDevice,
+    TextDisplay,
 }
 
 
@@ -41,6 +104,10 @@ impl Display for DeviceKernel {
         match self {
             Self::Cpu(cpu) => write!(f, "{cpu}"),
             Self::Serial(serial) => write!(f, "{serial}"),
+            Self::RealtimeClock(clock) => write!(f, "{clock}"),
+            Self::Timer(timer) => write!(f, "{timer}"),
+            Self::BlockDevice(disk) => write!(f, "{disk}"),
+            Self::TextDisplay(display) => write!(f, "{display}"),
         }
     }
 }
@@ -1,26 +1,137 @@
 use std::ascii::Char;
+use std::collections::VecDeque;
 use std::fmt::{Display, Formatter};
-use std::io::Write;
+use std::io::{Read, Write};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
 use crate::kernels::Kernel;
 
-#[derive(Debug, Clone, Default)]
+// where received input is sent to
+const CPU_ADDR: u8 = 0x00;
+const DEFAULT_INPUT_BUF: usize = 16;
+
+
+// what happens to input arriving when the buffer is already full
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Overflow {
+    // new input is lost
+    #[default]
+    Drop,
+    // oldest buffered input is lost
+    Overwrite,
+    // input is left with the host until there is room for it
+    Wait,
+}
+
+
+#[derive(Debug)]
 pub struct Serial {
     bus_rcv_buf: Option<(u8, u8)>,
     last_printed_c: Option<Char>,
+    input: Option<Receiver<u8>>,
+    input_buf: VecDeque<u8>,
+    input_buf_size: usize,
+    overflow: Overflow,
+    sending: bool,
+    dropped: u64,
+}
+
+
+impl Default for Serial {
+    fn default() -> Self {
+        Self {
+            bus_rcv_buf: None,
+            last_printed_c: None,
+            input: None,
+            input_buf: VecDeque::new(),
+            input_buf_size: DEFAULT_INPUT_BUF,
+            overflow: Overflow::default(),
+            sending: false,
+            dropped: 0,
+        }
+    }
 }
 
+
 impl Serial {
     pub fn new() -> Self {
         Default::default()
     }
+
+    // bytes coming from this get sent to cpu one by one
+    pub fn with_input(mut self, input: Receiver<u8>) -> Self {
+        self.input = Some(input);
+        self
+    }
+
+    pub fn with_input_buf(mut self, size: usize, overflow: Overflow) -> Self {
+        self.input_buf_size = size.max(1);
+        self.overflow = overflow;
+        self
+    }
+
+    // bytes lost to a full input buffer so far
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn pull_input(&mut self) {
+        let Some(input) = &self.input else { return };
+
+        loop {
+            if self.overflow == Overflow::Wait && self.input_buf.len() >= self.input_buf_size {
+                break;
+            };
+
+            match input.try_recv() {
+                Ok(b) => {
+                    if self.input_buf.len() < self.input_buf_size {
+                        self.input_buf.push_back(b);
+                    } else if self.overflow == Overflow::Overwrite {
+                        self.input_buf.pop_front();
+                        self.input_buf.push_back(b);
+                        self.dropped += 1;
+                    } else {
+                        self.dropped += 1;
+                    };
+                },
+                Err(TryRecvError::Empty) => { break; },
+                // host input is closed, whatever is buffered still gets sent
+                Err(TryRecvError::Disconnected) => {
+                    self.input = None;
+                    break;
+                },
+            };
+        };
+    }
 }
 
+
+// reads host stdin on a thread of its own so emulation never blocks on it
+pub fn stdin_input() -> Receiver<u8> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for b in std::io::stdin().lock().bytes() {
+            let Ok(b) = b else { break };
+            if tx.send(b).is_err() {
+                break;
+            };
+        };
+    });
+    rx
+}
+
+
 impl Display for Serial {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "last: {:?}", self.last_printed_c)
+        write!(f, "last: {:?} | input: {:?}", self.last_printed_c, self.input_buf)?;
+        if self.dropped != 0 {
+            write!(f, " | dropped: {}", self.dropped)?;
+        };
+        Ok(())
     }
 }
 
+
 impl Kernel for Serial {
     fn name(&self) -> &'static str {
         "serial"
@@ -29,13 +140,20 @@ impl Kernel for Serial {
     fn init_bus(&mut self, _addr: u8) {
         //
     }
-    
+
+    // one byte of input at a time, next one only once the previous got delivered
     fn send_bus_msg(&mut self) -> Option<(u8, u8)> {
-        None
+        if self.sending {
+            return None;
+        };
+
+        let b = self.input_buf.pop_front()?;
+        self.sending = true;
+        Some((b, CPU_ADDR))
     }
 
     fn end_send_bus_msg(&mut self) {
-        //
+        self.sending = false;
     }
 
     fn rcv_bus_msg(&mut self, msg: (u8, u8)) {
@@ -47,6 +165,11 @@ impl Kernel for Serial {
         self.bus_rcv_buf.is_none()
     }
 
+    // pending input does not count, nothing may ever read it
+    fn is_idle(&self) -> bool {
+        self.bus_rcv_buf.is_none()
+    }
+
     fn tick(&mut self) {
         self.last_printed_c = None;
         if let Some((msg, _)) = self.bus_rcv_buf.take()
@@ -55,5 +178,7 @@ impl Kernel for Serial {
             std::io::stdout().flush().unwrap();
             self.last_printed_c = Some(c);
         };
+
+        self.pull_input();
     }
 }
@@ -0,0 +1,170 @@
+use std::fmt::{Display, Formatter};
+use crate::kernels::Kernel;
+
+// programmed with pairs of messages, a command followed by its argument:
+// 0x01, 0x02: low, high byte of ticks until (first) expiry
+// 0x03, 0x04: low, high byte of ticks between expiries when repeating (0 is the same as until first one)
+// 0x05: mode, 0 stops the timer, 1 starts it once, 2 starts it repeating
+// each expiry is sent to whoever set the mode, as number of expiries so far (wrapping, so missed ones can be told)
+const CMD_COUNT_LO: u8 = 0x01;
+const CMD_COUNT_HI: u8 = 0x02;
+const CMD_RELOAD_LO: u8 = 0x03;
+const CMD_RELOAD_HI: u8 = 0x04;
+const CMD_MODE: u8 = 0x05;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimerMode {
+    Stopped,
+    OneShot,
+    Repeating,
+}
+
+
+impl TryFrom<u8> for TimerMode {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Stopped),
+            1 => Ok(Self::OneShot),
+            2 => Ok(Self::Repeating),
+            _ => Err(()),
+        }
+    }
+}
+
+
+impl Display for TimerMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stopped => write!(f, "stopped"),
+            Self::OneShot => write!(f, "one-shot"),
+            Self::Repeating => write!(f, "repeating"),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Timer {
+    count: u16,
+    reload: u16,
+    mode: TimerMode,
+    left: u16,
+    expiries: u8,
+    owner: u8,
+    cmd: Option<u8>,
+    bus_rcv_buf: Option<(u8, u8)>,
+    pending: bool,
+    sending: bool,
+}
+
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self { count: 0, reload: 0, mode: TimerMode::Stopped, left: 0, expiries: 0, owner: 0x00, cmd: None, bus_rcv_buf: None, pending: false, sending: false }
+    }
+}
+
+
+impl Timer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn execute(&mut self, cmd: u8, arg: u8, from: u8) {
+        match cmd {
+            CMD_COUNT_LO => { self.count = self.count & 0xFF00 | arg as u16; },
+            CMD_COUNT_HI => { self.count = self.count & 0x00FF | (arg as u16) << 8; },
+            CMD_RELOAD_LO => { self.reload = self.reload & 0xFF00 | arg as u16; },
+            CMD_RELOAD_HI => { self.reload = self.reload & 0x00FF | (arg as u16) << 8; },
+            // unknown modes are ignored
+            CMD_MODE => if let Ok(mode) = TimerMode::try_from(arg) {
+                self.mode = mode;
+                self.owner = from;
+                self.left = self.count.max(1);
+                self.expiries = 0;
+                self.pending = false;
+            },
+            _ => {},
+        };
+    }
+
+    fn expire(&mut self) {
+        self.expiries = self.expiries.wrapping_add(1);
+        self.pending = true;
+
+        match self.mode {
+            TimerMode::Repeating => {
+                self.left = if self.reload == 0 { self.count } else { self.reload }.max(1);
+            },
+            TimerMode::OneShot | TimerMode::Stopped => {
+                self.mode = TimerMode::Stopped;
+            },
+        };
+    }
+}
+
+
+impl Display for Timer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "mode: {} | left: {} | count: {} | reload: {} | expiries: {}", self.mode, self.left, self.count, self.reload, self.expiries)
+    }
+}
+
+
+impl Kernel for Timer {
+    fn name(&self) -> &'static str {
+        "timer"
+    }
+
+    fn init_bus(&mut self, _addr: u8) {
+        //
+    }
+
+    // expiries which happen before the previous one got sent are merged into it
+    fn send_bus_msg(&mut self) -> Option<(u8, u8)> {
+        if self.sending || !self.pending {
+            return None;
+        };
+
+        self.pending = false;
+        self.sending = true;
+        Some((self.expiries, self.owner))
+    }
+
+    fn end_send_bus_msg(&mut self) {
+        self.sending = false;
+    }
+
+    fn rcv_bus_msg(&mut self, msg: (u8, u8)) {
+        assert!(self.bus_rcv_buf.is_none());
+        self.bus_rcv_buf = Some(msg);
+    }
+
+    fn can_rcv_bus_msg(&self) -> bool {
+        self.bus_rcv_buf.is_none()
+    }
+
+    // a running timer does not count, it is not in flight until it expires
+    fn is_idle(&self) -> bool {
+        self.bus_rcv_buf.is_none()
+    }
+
+    fn tick(&mut self) {
+        if let Some((msg, from)) = self.bus_rcv_buf.take() {
+            match self.cmd.take() {
+                Some(cmd) => { self.execute(cmd, msg, from); },
+                None => { self.cmd = Some(msg); },
+            };
+        };
+
+        if self.mode != TimerMode::Stopped {
+            self.left -= 1;
+            if self.left == 0 {
+                self.expire();
+            };
+        };
+    }
+}
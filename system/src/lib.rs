@@ -3,10 +3,14 @@
 
 pub mod kernels;
 pub mod device;
+mod scheduler;
 
 use std::time::{Duration, Instant};
 use crate::device::Device;
-use crate::kernels::{Kernel, DeviceKernel};
+use crate::kernels::{Cpu, Kernel, DeviceKernel, Halt};
+use crate::scheduler::{Event, Scheduler};
+
+pub use scheduler::{RunOutcome, SchedulingError};
 
 struct Timer {
     delay: Duration,
@@ -47,6 +51,7 @@ pub struct System {
     bus_freq: u32,
     bus_timer: Timer,
     last_dev_locked_bus: Option<u8>,
+    scheduler: Option<Scheduler>,
 }
 
 
@@ -80,8 +85,29 @@ impl System {
             bus_freq,
             bus_timer: Timer::new(Duration::from_secs_f32(1.0 / bus_freq as f32)),
             last_dev_locked_bus: None,
+            scheduler: None,
         }
     }
+    
+    // system counts as halted only once everything that was in flight has been delivered
+    pub fn halt(&self) -> Option<Halt> {
+        if !self.devices.iter().flatten().all(|(dev, _)| dev.is_idle() || self.is_undeliverable(dev)) {
+            return None;
+        };
+        
+        self.devices.iter().flatten().find_map(|(dev, _)| dev.kernel.halt())
+    }
+    
+    // a message which is all that is left in flight but is for a halted device (eg input typed after cpu stopped)
+    fn is_undeliverable(&self, dev: &Device) -> bool {
+        dev.bus_msg_rcv.is_none()
+            && dev.kernel.is_idle()
+            && dev.bus_msg_send.is_some_and(|(_, to)| self.devices.get(to as usize).and_then(|d| d.as_ref()).is_some_and(|(d, _)| d.kernel.halt().is_some()))
+    }
+    
+    pub fn is_halted(&self) -> bool {
+        self.halt().is_some()
+    }
 
     pub fn tick_bus(&mut self) {
         let mut new_msg = None;
@@ -126,11 +152,15 @@ impl System {
         next_step.min(self.bus_timer.left())
     }
 
-    pub fn run(&mut self, dur: Option<Duration>) {
+    pub fn run(&mut self, dur: Option<Duration>) -> Option<Halt> {
         let mut runtime = Duration::new(0, 0); 
         
         let mut tick_delay = Duration::new(0, 0);
         while dur.is_none_or(|d| runtime < d) {
+            if let Some(halt) = self.halt() {
+                return Some(halt);
+            };
+            
             std::thread::sleep(tick_delay);
 
             // todo take into account how long did tick take
@@ -140,14 +170,20 @@ impl System {
                 runtime += tick_delay;
             };
         };
+        
+        None
     }
 
-    pub fn run_and_kill_cpu(&mut self, dur: Option<Duration>) {
+    pub fn run_and_kill_cpu(&mut self, dur: Option<Duration>) -> Option<Halt> {
         let mut runtime = Duration::new(0, 0);
         
         let mut tick_time = Duration::new(0, 0); 
         let start = Instant::now();
         while dur.is_none_or(|d| runtime < d) {
+            if let Some(halt) = self.halt() {
+                return Some(halt);
+            };
+            
             let _ = self.tick(tick_time);
             
             let end = Instant::now();
@@ -157,6 +193,84 @@ impl System {
             
             runtime = total_time;
         };
+        
+        None
     }
-}
 
+    fn run_virtual(&mut self, ticks: u64, stop_on_halt: bool, mut done: impl FnMut(&Self) -> bool) -> Result<RunOutcome, SchedulingError> {
+        let mut scheduler = match self.scheduler.take() {
+            Some(scheduler) => scheduler,
+            None => Scheduler::new(
+                self.devices.each_ref().map(|d| d.as_ref().map(|(dev, _)| dev.clock_freq)),
+                self.bus_freq,
+            )?,
+        };
+
+        let start = scheduler.ticks();
+        let deadline = scheduler.deadline(ticks);
+        let outcome = loop {
+            let ticks = scheduler.ticks() - start;
+            if let Some(addr) = self.breakpoint() {
+                break RunOutcome::Breakpoint { ticks, addr };
+            };
+            if stop_on_halt && let Some(halt) = self.halt() {
+                break RunOutcome::Halted { ticks, halt };
+            };
+            if done(self) {
+                break RunOutcome::Stepped { ticks };
+            };
+            
+            let Some(events) = scheduler.advance(deadline) else {
+                let ticks = scheduler.ticks() - start;
+                break match self.halt() {
+                    Some(halt) => RunOutcome::Halted { ticks, halt },
+                    None => RunOutcome::BudgetExhausted { ticks },
+                };
+            };
+            
+            for event in events {
+                match event {
+                    Event::Device(i) => { self.devices[i].as_mut().unwrap().0.tick(); },
+                    Event::Bus => { self.tick_bus(); },
+                };
+            };
+        };
+
+        self.scheduler = Some(scheduler);
+        Ok(outcome)
+    }
+
+    // runs in virtual time, so the result only depends on the program and clock frequencies
+    pub fn run_for_ticks(&mut self, ticks: u64) -> Result<RunOutcome, SchedulingError> {
+        self.run_virtual(ticks, false, |_| false)
+    }
+
+    pub fn run_until_halt(&mut self, max_ticks: u64) -> Result<RunOutcome, SchedulingError> {
+        self.run_virtual(max_ticks, true, |_| false)
+    }
+    
+    // runs until cpu has executed exactly one more instruction
+    pub fn step(&mut self, max_ticks: u64) -> Result<RunOutcome, SchedulingError> {
+        let before = self.cpu().map(|cpu| cpu.executed());
+        self.run_virtual(max_ticks, true, |sys| sys.cpu().map(|cpu| cpu.executed()) != before)
+    }
+    
+    pub fn cpu(&self) -> Option<&Cpu> {
+        self.devices.iter().flatten().find_map(|(dev, _)| match &dev.kernel {
+            DeviceKernel::Cpu(cpu) => Some(cpu),
+            _ => None,
+        })
+    }
+    
+    pub fn cpu_mut(&mut self) -> Option<&mut Cpu> {
+        self.devices.iter_mut().flatten().find_map(|(dev, _)| match &mut dev.kernel {
+            DeviceKernel::Cpu(cpu) => Some(cpu),
+            _ => None,
+        })
+    }
+    
+    // address of the breakpoint some device is paused at
+    pub fn breakpoint(&self) -> Option<u16> {
+        self.devices.iter().flatten().find_map(|(dev, _)| dev.kernel.breakpoint())
+    }
+}
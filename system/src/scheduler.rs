@@ -0,0 +1,158 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use crate::kernels::Halt;
+
+// deterministic scheduling on a virtual time base.
+// one unit of virtual time is 1/lcm(all clock freqs) of a second,
+// so every clock period is a whole number of units and no rounding ever happens
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunOutcome {
+    Halted { ticks: u64, halt: Halt },
+    Breakpoint { ticks: u64, addr: u16 },
+    Stepped { ticks: u64 },
+    BudgetExhausted { ticks: u64 },
+}
+
+
+impl RunOutcome {
+    pub fn ticks(&self) -> u64 {
+        match self {
+            Self::Halted { ticks, .. }
+            | Self::Breakpoint { ticks, .. }
+            | Self::Stepped { ticks }
+            | Self::BudgetExhausted { ticks } => *ticks,
+        }
+    }
+}
+
+
+impl Display for RunOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Halted { ticks, halt } => write!(f, "{halt} after {ticks} ticks"),
+            Self::Breakpoint { ticks, addr } => write!(f, "hit breakpoint at 0x{addr:0>4x} after {ticks} ticks"),
+            Self::Stepped { ticks } => write!(f, "stepped in {ticks} ticks"),
+            Self::BudgetExhausted { ticks } => write!(f, "tick budget exhausted after {ticks} ticks"),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchedulingError {
+    // lcm of all clock freqs does not fit the virtual time base
+    IncoherentClocks,
+}
+
+impl Display for SchedulingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IncoherentClocks => write!(f, "clock frequencies are too incoherent to be scheduled, their least common multiple overflows the time base"),
+        }
+    }
+}
+
+impl Error for SchedulingError {}
+
+
+pub(crate) struct Scheduler {
+    time: u64,  // everything before this point has been processed
+    tick_len: u64,
+    devs: [Option<Clock>; 16],
+    bus: Clock,
+}
+
+
+#[derive(Clone, Copy)]
+struct Clock {
+    period: u64,
+    next: u64,
+}
+
+
+impl Clock {
+    fn new(base: u64, freq: u32) -> Self {
+        Self { period: base / freq as u64, next: 0 }
+    }
+}
+
+
+pub(crate) enum Event {
+    Device(usize),
+    Bus,
+}
+
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    };
+    a
+}
+
+
+impl Scheduler {
+    pub fn new(dev_freqs: [Option<u32>; 16], bus_freq: u32) -> Result<Self, SchedulingError> {
+        let base = dev_freqs.iter()
+            .flatten()
+            .chain([&bus_freq])
+            .try_fold(1u64, |acc, &freq| {
+                let freq = freq as u64;
+                (acc / gcd(acc, freq)).checked_mul(freq)
+            })
+            .ok_or(SchedulingError::IncoherentClocks)?;
+
+        let fastest = dev_freqs.iter().flatten().chain([&bus_freq]).copied().max().unwrap();
+
+        Ok(Self {
+            time: 0,
+            tick_len: base / fastest as u64,
+            devs: dev_freqs.map(|freq| freq.map(|freq| Clock::new(base, freq))),
+            bus: Clock::new(base, bus_freq),
+        })
+    }
+
+    // one tick is one period of the fastest clock in the system,
+    // counts every tick which has (at least partially) happened
+    pub fn ticks(&self) -> u64 {
+        self.time.div_ceil(self.tick_len)
+    }
+
+    pub fn deadline(&self, ticks: u64) -> u64 {
+        self.ticks().saturating_add(ticks).saturating_mul(self.tick_len)
+    }
+
+    // all events which are due at the next point in time (devices first, in bus address order, then bus),
+    // or none if that point is at or past the deadline
+    pub fn advance(&mut self, deadline: u64) -> Option<Vec<Event>> {
+        let next = self.devs.iter()
+            .flatten()
+            .chain([&self.bus])
+            .map(|clk| clk.next)
+            .min()
+            .unwrap();
+
+        if next >= deadline {
+            self.time = deadline;
+            return None;
+        };
+
+        self.time = next + 1;
+
+        let mut events = Vec::new();
+        for (i, clk) in self.devs.iter_mut().enumerate().filter_map(|(i, clk)| Some((i, clk.as_mut()?))) {
+            if clk.next == next {
+                clk.next += clk.period;
+                events.push(Event::Device(i));
+            };
+        };
+
+        if self.bus.next == next {
+            self.bus.next += self.bus.period;
+            events.push(Event::Bus);
+        };
+
+        Some(events)
+    }
+}
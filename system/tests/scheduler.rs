@@ -0,0 +1,86 @@
+use system::kernels::{Cpu, HaltReason, Timer};
+use system::{DeviceDescription, RunOutcome, SchedulingError, System};
+use watto::Register;
+
+const RAM: u16 = 0x1000;
+
+// sets a one-shot timer going, waits for its expiry and exits with the number of expiries it got
+const PROGRAM: &str = "
+!macro send @b num
+    set $oa @b
+    set $oc #x02
+    iow
+    set $oa #xffff
+    ioww
+!endmacro
+
+    !m send #x01
+    !m send #d40
+    !m send #x02
+    !m send #x00
+    !m send #x05
+    !m send #x01
+
+    set $oc #x02
+    set $oa #xffff
+    iowr
+    set $oc #x02
+    ior
+    set $ob #x00ff
+    and
+    copy $oc $gd
+    stop
+";
+
+
+fn system(cpu_freq: u32, dev_freq: u32, bus_freq: u32) -> System {
+    let prog = wasp::assemble(PROGRAM, &wasp::Options::new()).unwrap().bytes();
+    System::new(vec![
+        DeviceDescription::new(0x00, Cpu::new(RAM, &prog).with_exit_reg(Some(Register::GeneralD)), cpu_freq, false),
+        DeviceDescription::new(0x02, Timer::new(), dev_freq, false),
+    ], bus_freq)
+}
+
+
+#[test]
+fn runs_are_reproducible() {
+    let first = system(1000, 300, 300).run_until_halt(100_000).unwrap();
+    let second = system(1000, 300, 300).run_until_halt(100_000).unwrap();
+    assert_eq!(first, second);
+
+    let RunOutcome::Halted { ticks, halt } = first else {
+        panic!("expected the program to halt, got: {first}");
+    };
+    assert!(ticks > 0);
+    assert_eq!(halt.reason, HaltReason::Stopped);
+    assert_eq!(halt.exit_code, Some(1));
+}
+
+
+#[test]
+fn splitting_a_run_does_not_change_it() {
+    let whole = system(1000, 300, 300).run_until_halt(100_000).unwrap();
+
+    let mut split = system(1000, 300, 300);
+    let mut ticks = 0;
+    let outcome = loop {
+        match split.run_until_halt(7).unwrap() {
+            RunOutcome::BudgetExhausted { ticks: n } => { ticks += n; },
+            outcome => break outcome,
+        };
+    };
+
+    let (RunOutcome::Halted { ticks: whole_ticks, halt: whole_halt }, RunOutcome::Halted { ticks: n, halt }) = (whole, outcome) else {
+        panic!("expected both runs to halt");
+    };
+    assert_eq!(ticks + n, whole_ticks);
+    assert_eq!(halt, whole_halt);
+}
+
+
+#[test]
+fn incoherent_clocks_are_reported() {
+    // pairwise coprime, their least common multiple does not fit into 64 bits
+    let mut system = system(4_294_967_291, 4_294_967_279, 4_294_967_231);
+    assert_eq!(system.run_until_halt(1000), Err(SchedulingError::IncoherentClocks));
+}
@@ -10,7 +10,7 @@ pub struct AsmArgs {
     #[arg(long)]
     pub dry: bool,
     
-    /// path to the source file
+    /// path to the source file (or to the binary when disassembling)
     #[arg(long, short, value_parser = clap::value_parser!(ClioPath).exists().is_file(), default_value = "-")]
     pub source: ClioPath,
     
@@ -28,16 +28,61 @@ pub struct AsmArgs {
     
     /// output format
     #[arg(long, default_value_t)]
-    pub format: Format
+    pub format: Format,
+    
+    /// enable a feature for `!iffeat` (can be given multiple times)
+    #[arg(long = "feature", short = 'F')]
+    pub features: Vec<String>,
+    
+    /// set an environment value for `!ifenv` as NAME=value (can be given multiple times)
+    #[arg(long = "define", short = 'D', value_parser = parse_define)]
+    pub defines: Vec<(String, String)>,
+    
+    /// ram size in bytes, everything placed has to fit into it
+    #[arg(long)]
+    pub ram: Option<u16>,
+    
+    /// label at which execution starts (address 0 if not given), for executables and images that can hold it
+    #[arg(long)]
+    pub entry: Option<String>,
+    
+    /// do not embed symbols into the executable (or non-exported ones into the object)
+    #[arg(long, default_value_t = false)]
+    pub strip: bool,
+    
+    /// also write a symbol map (label, address, where it is defined) to this path
+    #[arg(long, value_parser = clap::value_parser!(ClioPath))]
+    pub symbols: Option<ClioPath>,
+    
+    /// also write a listing (address, bytes, source line) to this path
+    #[arg(long, value_parser = clap::value_parser!(ClioPath))]
+    pub listing: Option<ClioPath>,
+}
+
+fn parse_define(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(name, val)| (name.to_string(), val.to_string()))
+        .ok_or_else(|| format!("expected NAME=value, got: {s}"))
 }
 
+
 #[derive(Clone, Debug, Default, ValueEnum)]
 pub enum Format {
     Words,
     Elements,
     Instructs,
-    #[default]
     Binary,
+    #[default]
+    Executable,
+    Object,
+    IntelHex,
+    Srec,
+    Logisim,
+    Readmemh,
+    CArray,
+    RustArray,
+    Disasm,
+    Isa,
 }
 
 
@@ -48,6 +93,16 @@ impl Display for Format {
             Self::Elements => write!(f, "elements"),
             Self::Instructs => write!(f, "instructs"),
             Self::Binary => write!(f, "binary"),
+            Self::Executable => write!(f, "executable"),
+            Self::Object => write!(f, "object"),
+            Self::IntelHex => write!(f, "intel-hex"),
+            Self::Srec => write!(f, "srec"),
+            Self::Logisim => write!(f, "logisim"),
+            Self::Readmemh => write!(f, "readmemh"),
+            Self::CArray => write!(f, "c-array"),
+            Self::RustArray => write!(f, "rust-array"),
+            Self::Disasm => write!(f, "disasm"),
+            Self::Isa => write!(f, "isa"),
         }
     }
 }
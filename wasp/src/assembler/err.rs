@@ -1,6 +1,6 @@
 use std::error::Error;
 use std::fmt::{Display, Formatter};
-use crate::processor::Instruct;
+use crate::processor::{Instruct, Label};
 
 #[derive(Debug, Clone)]
 pub enum AssemblingError<PE>
@@ -8,6 +8,15 @@ pub enum AssemblingError<PE>
 {
     ProcessingError(PE),
     ProgTooLarge,
+    Overlap {
+        instruct: Instruct,
+        other: Instruct,
+    },
+    ExceedsRam {
+        instruct: Instruct,
+        end: usize,
+        ram: u16,
+    },
     InvalidInstruct {
         instruct: Instruct,
         info: InvalidInstructInfo
@@ -34,26 +43,78 @@ impl<PE> Display for AssemblingError<PE>
         match self {
             Self::ProcessingError(_) => write!(f, "processing error occurred"),
             Self::ProgTooLarge => write!(f, "program too large to fit within 64KiB"),
+            Self::Overlap { instruct, .. } => write!(f, "code placed from ({instruct}) overlaps other code"),
+            Self::ExceedsRam { instruct, end, ram } => write!(f, "code placed from ({instruct}) ends at 0x{end:0>4x}, past the end of {ram} bytes of ram"),
             Self::InvalidInstruct { instruct, info } => write!(f, "invalid instruct ({instruct}): {info}"),
         }
     }
 }
 
 
+// everything found wrong with a program, it is assembled only if there are no errors
+#[derive(Debug)]
+pub struct AssemblingErrors<PE>
+    where PE: Error + Clone + 'static
+{
+    pub errors: Vec<AssemblingError<PE>>,
+    pub warnings: Vec<AssemblingWarning>,
+}
+
+
+// suspicious but still assembled
+#[derive(Debug, Clone)]
+pub enum AssemblingWarning {
+    DuplicateLabel {
+        label: Label,
+        first: Label,
+    },
+}
+
+
+impl Display for AssemblingWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DuplicateLabel { label, .. } => write!(f, "label `{}` is defined more than once, the last definition is used", label.name),
+        }
+    }
+}
+
+
+impl Error for AssemblingWarning {}
+
+
 #[derive(Debug, Clone)]
 pub enum InvalidInstructInfo {
     ModifyingLabel,
     ReferenceOutOfBounds,
     UnknownVariable,
+    DivisionByZero,
+    UnresolvedImport,
+    NotRelocatable,
+    ImportingLabel,
+    UnknownExport,
+    OrgInObject,
+    AlignInObject,
+    SectionInObject,
+    DataInBss,
 }
 
 
 impl Display for InvalidInstructInfo {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::ModifyingLabel => write!(f, "reassigning label is forbidden"),
+            Self::ModifyingLabel => write!(f, "reassigning label or import is forbidden"),
             Self::ReferenceOutOfBounds => write!(f, "reference leads to non-existent instruction"),
             Self::UnknownVariable => write!(f, "such variable does not exist"),
+            Self::DivisionByZero => write!(f, "division by zero in expression"),
+            Self::UnresolvedImport => write!(f, "imported symbol is only known once linked (assemble as object)"),
+            Self::NotRelocatable => write!(f, "expression can't be relocated, only address plus/minus constant, its hi/lo or difference of two addresses can"),
+            Self::ImportingLabel => write!(f, "label defined here can't be imported"),
+            Self::UnknownExport => write!(f, "only labels can be exported"),
+            Self::OrgInObject => write!(f, "objects are placed by the linker, they can't have fixed addresses"),
+            Self::AlignInObject => write!(f, "objects are placed by the linker at any address, alignment within them would not hold"),
+            Self::SectionInObject => write!(f, "objects are placed by the linker as a whole, they can't be split into sections"),
+            Self::DataInBss => write!(f, "bss only reserves space, it can't hold anything but zeros"),
         }
     }
 }
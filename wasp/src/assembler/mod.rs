@@ -1,7 +1,11 @@
 use std::collections::HashMap;
 use std::error::Error;
-use err::{AssemblingError, InvalidInstructInfo};
-use crate::processor::{Argument, Instruct, Op, ValueArgument};
+use watto::{InstructionId, Object, Relocation, RelocationKind, RelocationTarget, Segment, Symbol};
+use err::InvalidInstructInfo;
+use crate::parser::Operator;
+use crate::processor::{Argument, Expr, Function, Instruct, Label, Op, Section, ValueArgument};
+
+pub use err::{AssemblingError, AssemblingErrors, AssemblingWarning};
 
 mod err;
 
@@ -9,7 +13,9 @@ pub struct Assembler<P, PE>
     where P: Iterator<Item = Result<Instruct, PE>>,
         PE: Error + Clone + 'static
 {
-    processor: P
+    processor: P,
+    relocatable: bool,
+    ram: Option<u16>,
 }
 
 
@@ -18,100 +24,454 @@ impl<P, PE> Assembler<P, PE>
           PE: Error + Clone + 'static
 {
     pub fn new(processor: P) -> Self {
-        Self { processor }
+        Self { processor, relocatable: false, ram: None }
+    }
+    
+    // assembles for an object, label addresses become relative to the start of the code
+    // and words depending on them (or on imports) get relocations
+    pub fn with_relocations(mut self, relocatable: bool) -> Self {
+        self.relocatable = relocatable;
+        self
     }
     
-    // xxx should it assemble into some Program(Vec<Instruction>)?
-    pub fn assemble(mut self) -> Result<Vec<u8>, AssemblingError<PE>> {
-        let instructs = self.processor.try_collect::<Vec<_>>().map_err(AssemblingError::ProcessingError)?;
+    // everything placed has to fit below this address
+    pub fn with_ram(mut self, size: Option<u16>) -> Self {
+        self.ram = size;
+        self
+    }
+    
+    // goes on past errors to report as many of them as it can find
+    pub fn assemble(self) -> Result<Program, AssemblingErrors<PE>> {
+        let mut errors = Vec::new();
+        let mut warnings = Vec::new();
+        let mut instructs = Vec::new();
+        for instruct in self.processor {
+            match instruct {
+                Ok(instruct) => instructs.push(instruct),
+                Err(err) => errors.push(AssemblingError::ProcessingError(err)),
+            };
+        };
         
-        let (mut variables, addrs) = {
+        // switching sections belongs to the new one, so do the labels before it
+        let mut section = Section::Text;
+        let sections = instructs.iter().map(|instruct| {
+            if let Op::Section(new) = instruct.operation() {
+                section = *new;
+            };
+            section
+        }).collect::<Vec<_>>();
+        
+        // sections are laid out one after another, each continuing where the previous one ended
+        // (unless moved by `!org`, which also starts a new chunk)
+        let (mut variables, addrs, mut chunks, chunk_of, symbols, imports) = {
             let mut variables = HashMap::new();
-            let mut addrs = Vec::new();
-            let mut cur_addr = 0u16;
-            for instruct in instructs.iter() {
-                for label in instruct.labels() {
-                    variables.insert(label.clone(), Variable { value: cur_addr, is_label: true });
+            let mut defined: HashMap<&str, &Label> = HashMap::new();
+            let mut addrs = vec![(0u16, 0u16); instructs.len()];
+            let mut chunks: Vec<Chunk> = Vec::new();
+            let mut chunk_of = vec![0; instructs.len()];
+            let mut symbols = Vec::new();
+            let mut cur_addr = 0usize;
+            for section in Section::ALL {
+                let mut chunk = None;
+                for (i, instruct) in instructs.iter().enumerate().filter(|(i, _)| sections[*i] == section) {
+                    if let Op::Org(addr) = instruct.operation() {
+                        if self.relocatable {
+                            errors.push(AssemblingError::InvalidInstruct { instruct: instruct.clone(), info: InvalidInstructInfo::OrgInObject });
+                        };
+                        cur_addr = *addr as usize;
+                        chunk = None;
+                    };
+                    if let Op::Align(_) = instruct.operation() && self.relocatable {
+                        errors.push(AssemblingError::InvalidInstruct { instruct: instruct.clone(), info: InvalidInstructInfo::AlignInObject });
+                    };
+                    if let Op::Section(_) = instruct.operation() && self.relocatable {
+                        errors.push(AssemblingError::InvalidInstruct { instruct: instruct.clone(), info: InvalidInstructInfo::SectionInObject });
+                    };
+                    let chunk = *chunk.get_or_insert_with(|| {
+                        chunks.push(Chunk { section, first: i, start: cur_addr, end: cur_addr, bytes: Vec::new() });
+                        chunks.len() - 1
+                    });
+                    
+                    for label in instruct.labels() {
+                        if let Some(first) = defined.insert(&label.name, label) {
+                            warnings.push(AssemblingWarning::DuplicateLabel { label: label.clone(), first: first.clone() });
+                        };
+                        variables.insert(label.name.clone(), Variable { value: cur_addr as u16, kind: VariableKind::Label });
+                        symbols.push(Symbol { name: label.name.clone(), addr: cur_addr as u16 });
+                    };
+                    
+                    let instr_size = match instruct.operation() {
+                        Op::Align(n) => (*n as usize - cur_addr % *n as usize) % *n as usize,
+                        op => op.size(),
+                    };
+                    
+                    addrs[i] = (cur_addr as u16, instr_size as u16);
+                    chunk_of[i] = chunk;
+                    
+                    cur_addr += instr_size;
+                    if cur_addr > u16::MAX as usize {
+                        errors.push(AssemblingError::ProgTooLarge);
+                        return Err(AssemblingErrors { errors, warnings });
+                    };
+                    chunks[chunk].end = cur_addr;
                 };
-
-                let instr_size = instruct.operation().size() as u16;
-
-                addrs.push((cur_addr, instr_size));
-
-                if let Some(new_addr) = cur_addr.checked_add(instr_size) {
-                    cur_addr = new_addr
-                } else {
-                    return Err(AssemblingError::ProgTooLarge);
+            };
+            
+            let mut imports: Vec<&String> = Vec::new();
+            for instruct in instructs.iter() {
+                if let Op::Import(name) = instruct.operation() && !imports.contains(&name) {
+                    if variables.contains_key(name) {
+                        errors.push(AssemblingError::InvalidInstruct { instruct: instruct.clone(), info: InvalidInstructInfo::ImportingLabel });
+                    } else {
+                        variables.insert(name.clone(), Variable { value: 0, kind: VariableKind::Import });
+                        imports.push(name);
+                    };
                 };
             };
-            (variables, addrs)
+            
+            (variables, addrs, chunks, chunk_of, symbols, imports.into_iter().cloned().collect::<Vec<_>>())
+        };
+        
+        // chunks may only overlap if either is empty
+        let mut placed = chunks.iter().filter(|chunk| chunk.start < chunk.end).collect::<Vec<_>>();
+        placed.sort_by_key(|chunk| chunk.start);
+        let mut furthest: Option<&Chunk> = None;
+        for chunk in placed {
+            if let Some(other) = furthest && chunk.start < other.end {
+                errors.push(AssemblingError::Overlap { instruct: instructs[chunk.first].clone(), other: instructs[other.first].clone() });
+            };
+            if furthest.is_none_or(|other| chunk.end > other.end) {
+                furthest = Some(chunk);
+            };
+            
+            if let Some(ram) = self.ram && chunk.end > ram as usize {
+                errors.push(AssemblingError::ExceedsRam { instruct: instructs[chunk.first].clone(), end: chunk.end, ram });
+            };
         };
         
-        let mut prog = Vec::new();
-        for (i, instruct) in instructs.into_iter().enumerate() {
-            let pos = instruct.pos();
-            let labels = instruct.labels().to_vec();
-            match instruct.into_operation() {
+        let mut relocations = Vec::new();
+        let mut exports = Vec::new();
+        for (i, instruct) in instructs.iter().enumerate() {
+            let layout = Layout { addrs: &addrs, variables: &variables, imports: &imports, relocatable: self.relocatable };
+            let mut bytes = Vec::new();
+            let mut relocs = Vec::new();
+            match instruct.operation() {
                 Op::InsertCpuInstruction(id, args) => {
-                    prog.push(id.code());
-
-                    for arg in args.iter() {
-                        match arg {
-                            Argument::Register(reg) => { prog.push(reg.to_addr()); }
-                            Argument::Value(val) =>
-                                match val {
-                                    ValueArgument::Literal(n) => { prog.extend(n.to_le_bytes()) },
-                                    ValueArgument::Reference(delta) => {
-                                        #[allow(clippy::collapsible_else_if)]
-                                        if *delta > 0 {
-                                            if let Some(addr) = addrs.get((i as isize + *delta as isize - 1) as usize) {
-                                                prog.extend((addr.0 + addr.1).to_le_bytes());
-                                            } else {
-                                                return Err(AssemblingError::InvalidInstruct { instruct: Instruct::new(pos, labels, Op::InsertCpuInstruction(id, args)), info: InvalidInstructInfo::ReferenceOutOfBounds });
-                                            };
-                                        } else {
-                                            if i >= delta.unsigned_abs() as usize
-                                                && let Some(addr) = addrs.get((i as isize + *delta as isize) as usize) {
-                                                prog.extend(addr.0.to_le_bytes());
-                                            } else {
-                                                return Err(AssemblingError::InvalidInstruct { instruct: Instruct::new(pos, labels, Op::InsertCpuInstruction(id, args)), info: InvalidInstructInfo::ReferenceOutOfBounds });
-                                            };
-                                        };
-                                    },
-                                    ValueArgument::Variable(name) => {
-                                        if let Some(val) = variables.get(name) {
-                                            prog.extend(val.value.to_le_bytes());
-                                        } else {
-                                            return Err(AssemblingError::InvalidInstruct { instruct: Instruct::new(pos, labels, Op::InsertCpuInstruction(id, args)), info: InvalidInstructInfo::UnknownVariable });
-                                        };
-                                    },
-                                },
+                    if let Err(info) = encode(&mut bytes, &mut relocs, *id, args, i, &layout) {
+                        errors.push(AssemblingError::InvalidInstruct { instruct: instruct.clone(), info });
+                    };
+                },
+                // arguments within are relative to the pseudo-instruction as a whole
+                Op::InsertPseudo(_, _, instrs) => {
+                    for (id, args) in instrs {
+                        if let Err(info) = encode(&mut bytes, &mut relocs, *id, args, i, &layout) {
+                            errors.push(AssemblingError::InvalidInstruct { instruct: instruct.clone(), info });
                         };
                     };
                 },
                 Op::SetVariable(name, value) => {
-                    if let Some(Variable { is_label: true, .. }) = variables.get(&name) {
-                        return Err(AssemblingError::InvalidInstruct { instruct: Instruct::new(pos, labels, Op::SetVariable(name, value)), info: InvalidInstructInfo::ModifyingLabel });
+                    if let Some(Variable { kind: VariableKind::Label | VariableKind::Import, .. }) = variables.get(name) {
+                        errors.push(AssemblingError::InvalidInstruct { instruct: instruct.clone(), info: InvalidInstructInfo::ModifyingLabel });
+                        continue;
                     };
 
-                    variables.entry(name)
-                        .and_modify(|v| v.value = value)
-                        .or_insert(Variable { value, is_label: false });
+                    variables.entry(name.clone())
+                        .and_modify(|v| v.value = *value)
+                        .or_insert(Variable { value: *value, kind: VariableKind::Constant });
+                },
+                Op::Export(name) => match variables.get(name) {
+                    Some(Variable { value, kind: VariableKind::Label }) => { exports.push(Symbol { name: name.clone(), addr: *value }); },
+                    _ => { errors.push(AssemblingError::InvalidInstruct { instruct: instruct.clone(), info: InvalidInstructInfo::UnknownExport }); },
                 },
-                Op::InsertByte(b) => { prog.push(b); },
-                Op::InsertWord(w) => { prog.extend(w.to_le_bytes()); },
-                Op::InsertBytes(mut bytes) => { prog.append(&mut bytes); },
-                Op::InsertMultipleBytes(b, count) => { (0..count).for_each(|_| prog.push(b)) ;},
-                Op::InsertCString(cstr) => { prog.extend(cstr.into_bytes_with_nul()); },
+                Op::Align(_) => { bytes.resize(addrs[i].1 as usize, 0); },
+                Op::Import(_) | Op::Org(_) | Op::Section(_) => {},
+                Op::InsertByte(b) => { bytes.push(*b); },
+                Op::InsertWord(w) => { bytes.extend(w.to_le_bytes()); },
+                Op::InsertBytes(data) => { bytes.extend_from_slice(data); },
+                Op::InsertMultipleBytes(b, count) => { (0..*count).for_each(|_| bytes.push(*b)) ;},
+                Op::InsertCString(cstr) => { bytes.extend(cstr.as_bytes_with_nul()); },
                 Op::Void => {}
             }
+            
+            let chunk = &mut chunks[chunk_of[i]];
+            if chunk.section == Section::Bss {
+                if bytes.iter().any(|b| *b != 0) {
+                    errors.push(AssemblingError::InvalidInstruct { instruct: instruct.clone(), info: InvalidInstructInfo::DataInBss });
+                };
+                continue;
+            };
+            
+            relocations.extend(relocs.into_iter().map(|reloc| Relocation { offset: reloc.offset + addrs[i].0, ..reloc }));
+            chunk.bytes.extend(bytes);
+        };
+        
+        if !errors.is_empty() {
+            return Err(AssemblingErrors { errors, warnings });
         };
-        Ok(prog)
+        
+        let segments = chunks.into_iter()
+            .filter(|chunk| chunk.start < chunk.end)
+            .map(|chunk| match chunk.section {
+                Section::Bss => Segment { addr: chunk.start as u16, size: chunk.end - chunk.start, data: Vec::new() },
+                _ => Segment::new(chunk.start as u16, chunk.bytes),
+            })
+            .collect();
+
+        let placements = instructs.into_iter()
+            .zip(addrs)
+            .map(|(instruct, (addr, size))| Placement { addr, size, instruct })
+            .collect();
+
+        Ok(Program { segments, symbols, placements, warnings, relocations, exports, imports })
     }
 }
 
 
+pub struct Program {
+    pub segments: Vec<Segment>,  // bss ones have no data
+    pub symbols: Vec<Symbol>,
+    pub placements: Vec<Placement>,
+    pub warnings: Vec<AssemblingWarning>,
+    pub relocations: Vec<Relocation>,  // only when assembled with relocations
+    pub exports: Vec<Symbol>,
+    pub imports: Vec<String>,
+}
+
+
+// where an instruct ended up in the program
+pub struct Placement {
+    pub addr: u16,
+    pub size: u16,
+    pub instruct: Instruct,
+}
+
+
+impl Program {
+    pub fn symbol(&self, name: &str) -> Option<u16> {
+        self.symbols.iter().find(|sym| sym.name == name).map(|sym| sym.addr)
+    }
+    
+    pub fn placed_bytes(&self, placement: &Placement) -> &[u8] {
+        let (start, end) = (placement.addr as usize, placement.addr as usize + placement.size as usize);
+        self.segments.iter()
+            .find(|seg| seg.addr as usize <= start && end <= seg.addr as usize + seg.data.len())
+            .map_or(&[], |seg| &seg.data[start - seg.addr as usize..end - seg.addr as usize])
+    }
+    
+    // memory from address 0 up to the last stored byte, gaps are zeroed
+    pub fn image(&self) -> Vec<u8> {
+        let mut image = vec![0; self.segments.iter().map(|seg| seg.addr as usize + seg.data.len()).max().unwrap_or(0)];
+        for seg in self.segments.iter() {
+            image[seg.addr as usize..][..seg.data.len()].copy_from_slice(&seg.data);
+        };
+        image
+    }
+    
+    // objects have a single section (see `SectionInObject`), so their code is the whole image
+    pub fn object(&self) -> Object {
+        let code = self.image();
+        
+        Object {
+            exports: self.exports.clone(),
+            imports: self.imports.clone(),
+            relocations: self.relocations.clone(),
+            symbols: Some(self.symbols.clone()),
+            ..Object::new(code)
+        }
+    }
+}
+
+
+// instructs of a section placed back to back
+struct Chunk {
+    section: Section,
+    first: usize,  // instruct it starts with
+    start: usize,
+    end: usize,
+    bytes: Vec<u8>,
+}
+
+
 struct Variable {
     value: u16,
-    is_label: bool,
+    kind: VariableKind,
+}
+
+
+#[derive(PartialEq)]
+enum VariableKind {
+    Constant,
+    Label,
+    Import,  // only known once linked
+}
+
+
+// everything the arguments of an instruct are evaluated against
+struct Layout<'a> {
+    addrs: &'a [(u16, u16)],
+    variables: &'a HashMap<String, Variable>,
+    imports: &'a [String],
+    relocatable: bool,
+}
+
+
+// evaluated argument along with what it is relative to
+struct Value {
+    n: u16,  // as if the code and every import were at address 0
+    base: Base,
+    kind: RelocationKind,  // which part of the address is used, other than abs16 only for relative values
+}
+
+
+#[derive(PartialEq)]
+enum Base {
+    Absolute,
+    Code,
+    Import(String),
+    Unrelocatable,  // depends on addresses in a way a linker can't fix up, eg a label times two
+}
+
+
+impl Value {
+    fn absolute(n: u16) -> Self {
+        Self { n, base: Base::Absolute, kind: RelocationKind::Abs16 }
+    }
+    
+    fn relative(n: u16, base: Base) -> Self {
+        Self { n, base, kind: RelocationKind::Abs16 }
+    }
+    
+    fn word(&self) -> u16 {
+        self.kind.apply(self.n)
+    }
+    
+    // the whole address plus some offset
+    fn is_plain(&self) -> bool {
+        self.kind == RelocationKind::Abs16 && matches!(self.base, Base::Code | Base::Import(_))
+    }
+}
+
+
+// cpu instruction of the i-th instruct, arguments which can't be evaluated are left zeroed
+// to keep the following instructs where they belong
+fn encode(prog: &mut Vec<u8>, relocations: &mut Vec<Relocation>, id: InstructionId, args: &[Argument], i: usize, layout: &Layout) -> Result<(), InvalidInstructInfo> {
+    prog.push(id.code());
+
+    let mut res = Ok(());
+    for arg in args.iter() {
+        match arg {
+            Argument::Register(reg) => { prog.push(reg.to_addr()); }
+            Argument::Value(val) => match eval(val, i, layout) {
+                Ok(value) => {
+                    let offset = prog.len() as u16;
+                    prog.extend(value.word().to_le_bytes());
+                    
+                    let target = match &value.base {
+                        Base::Absolute => { continue; },
+                        Base::Code => RelocationTarget::Code,
+                        Base::Import(name) => RelocationTarget::Import(layout.imports.iter().position(|import| import == name).unwrap() as u16),
+                        Base::Unrelocatable => {
+                            res = res.and(Err(InvalidInstructInfo::NotRelocatable));
+                            continue;
+                        },
+                    };
+                    relocations.push(Relocation { offset, kind: value.kind, target, addend: value.n });
+                },
+                Err(info) => {
+                    prog.extend([0, 0]);
+                    res = res.and(Err(info));
+                },
+            },
+        };
+    };
+
+    res
+}
+
+
+// value of an argument of the i-th instruct
+fn eval(val: &ValueArgument, i: usize, layout: &Layout) -> Result<Value, InvalidInstructInfo> {
+    let addr = |n| if layout.relocatable { Value::relative(n, Base::Code) } else { Value::absolute(n) };
+    let addrs = layout.addrs;
+    
+    match val {
+        ValueArgument::Literal(n) => Ok(Value::absolute(*n)),
+        ValueArgument::Reference(delta) => {
+            #[allow(clippy::collapsible_else_if)]
+            if *delta > 0 {
+                addrs.get((i as isize + *delta as isize - 1) as usize)
+                    .map(|addr| addr.0 + addr.1)
+                    .map(addr)
+                    .ok_or(InvalidInstructInfo::ReferenceOutOfBounds)
+            } else {
+                if i >= delta.unsigned_abs() as usize
+                    && let Some(&(n, _)) = addrs.get((i as isize + *delta as isize) as usize) {
+                    Ok(addr(n))
+                } else {
+                    Err(InvalidInstructInfo::ReferenceOutOfBounds)
+                }
+            }
+        },
+        ValueArgument::Variable(name) => match layout.variables.get(name) {
+            Some(Variable { value, kind: VariableKind::Constant }) => Ok(Value::absolute(*value)),
+            Some(Variable { value, kind: VariableKind::Label }) => Ok(addr(*value)),
+            Some(Variable { kind: VariableKind::Import, .. }) if layout.relocatable => Ok(Value::relative(0, Base::Import(name.clone()))),
+            Some(Variable { kind: VariableKind::Import, .. }) => Err(InvalidInstructInfo::UnresolvedImport),
+            None => Err(InvalidInstructInfo::UnknownVariable),
+        },
+        ValueArgument::Expression(expr) => eval_expr(expr, i, layout),
+    }
+}
+
+
+// arithmetic wraps around as it does in the cpu, only sums and differences stay relocatable
+fn eval_expr(expr: &Expr, i: usize, layout: &Layout) -> Result<Value, InvalidInstructInfo> {
+    let eval_expr = |expr| eval_expr(expr, i, layout);
+    let derived = |n, val: &Value| Value::relative(n, if val.base == Base::Absolute { Base::Absolute } else { Base::Unrelocatable });
+
+    Ok(match expr {
+        Expr::Value(val) => eval(val, i, layout)?,
+        Expr::Neg(expr) => {
+            let val = eval_expr(expr)?;
+            derived(val.word().wrapping_neg(), &val)
+        },
+        Expr::Call(func, arg) => {
+            let val = eval_expr(arg)?;
+            let kind = match func {
+                Function::Hi => RelocationKind::Hi,
+                Function::Lo => RelocationKind::Lo,
+            };
+            if val.is_plain() {
+                Value { kind, ..val }
+            } else {
+                derived(kind.apply(val.word()), &val)
+            }
+        },
+        Expr::Binary(op, lhs, rhs) => {
+            let (lhs, rhs) = (eval_expr(lhs)?, eval_expr(rhs)?);
+            let (l, r) = (lhs.word(), rhs.word());
+            let n = match op {
+                Operator::Add => l.wrapping_add(r),
+                Operator::Sub => l.wrapping_sub(r),
+                Operator::Mul => l.wrapping_mul(r),
+                Operator::Div => l.checked_div(r).ok_or(InvalidInstructInfo::DivisionByZero)?,
+                Operator::And => l & r,
+                Operator::Or => l | r,
+                Operator::Xor => l ^ r,
+                Operator::Shl => l.checked_shl(r as u32).unwrap_or(0),
+                Operator::Shr => l.checked_shr(r as u32).unwrap_or(0),
+                Operator::LeftParen | Operator::RightParen => unreachable!("parentheses are not binary operators"),
+            };
+            
+            let base = match (op, lhs, rhs) {
+                (_, Value { base: Base::Absolute, .. }, Value { base: Base::Absolute, .. }) => Base::Absolute,
+                (Operator::Add | Operator::Sub, val, Value { base: Base::Absolute, .. }) if val.is_plain() => val.base,
+                (Operator::Add, Value { base: Base::Absolute, .. }, val) if val.is_plain() => val.base,
+                // distance between two addresses relative to the same thing
+                (Operator::Sub, lhs, rhs) if lhs.is_plain() && rhs.is_plain() && lhs.base == rhs.base => Base::Absolute,
+                _ => Base::Unrelocatable,
+            };
+            Value::relative(n, base)
+        },
+    })
 }
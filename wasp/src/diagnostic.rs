@@ -0,0 +1,271 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use crate::assembler::{AssemblingError, AssemblingWarning};
+use crate::lexer::{LexingError, Pos};
+use crate::parser::ParsingError;
+use crate::processor::{InvalidElementInfo, ProcessingError};
+
+
+#[derive(Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub source: String,
+}
+
+
+// a macro being called, elements substituted into it carry its id in their position
+#[derive(Clone)]
+pub struct Expansion {
+    pub name: String,
+    pub call: Pos,
+}
+
+
+// every source (file, included file or macro string) and macro expansion positions can refer to,
+// the main source is expected to be added first as positions default to file 0
+#[derive(Default, Clone)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    expansions: Vec<Expansion>,
+    virtual_root: bool,  // paths are within a virtual filesystem, shown relative to its `/` instead of the working directory
+}
+
+
+impl SourceMap {
+    pub fn with_virtual_root(mut self, virtual_root: bool) -> Self {
+        self.virtual_root = virtual_root;
+        self
+    }
+
+    pub fn add_file(&mut self, name: String, source: String) -> usize {
+        self.files.push(SourceFile { name, source });
+        self.files.len() - 1
+    }
+
+    // included files come resolved to absolute paths, relative ones show them the same way as the main file
+    pub fn add_path(&mut self, path: Option<&PathBuf>, source: String) -> usize {
+        let base = if self.virtual_root { Some(PathBuf::from("/")) } else { std::env::current_dir().ok() };
+        let name = match path {
+            Some(path) => base.as_ref()
+                .and_then(|base| path.strip_prefix(base).ok())
+                .unwrap_or(path)
+                .display()
+                .to_string(),
+            None => String::from("-"),
+        };
+        self.add_file(name, source)
+    }
+
+    // ids start at 1, 0 means not expanded
+    pub fn add_expansion(&mut self, name: String, call: Pos) -> usize {
+        self.expansions.push(Expansion { name, call });
+        self.expansions.len()
+    }
+
+    pub fn file(&self, id: usize) -> Option<&SourceFile> {
+        self.files.get(id)
+    }
+
+    pub fn expansion(&self, id: usize) -> Option<&Expansion> {
+        id.checked_sub(1).and_then(|i| self.expansions.get(i))
+    }
+
+    pub fn line(&self, pos: Pos) -> Option<&str> {
+        self.file(pos.file())?.source.lines().nth(pos.line())
+    }
+
+    // `file:line:col`
+    pub fn location(&self, pos: Pos) -> String {
+        let file = self.file(pos.file()).map_or("?", |file| file.name.as_str());
+        format!("{file}:{}:{}", pos.line() + 1, pos.column() + 1)
+    }
+}
+
+
+// all items, or all errors if there were any
+pub fn collect_all<T, E>(iter: impl Iterator<Item = Result<T, E>>) -> Result<Vec<T>, Vec<E>> {
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    for item in iter {
+        match item {
+            Ok(item) => items.push(item),
+            Err(err) => errors.push(err),
+        };
+    };
+
+    if errors.is_empty() { Ok(items) } else { Err(errors) }
+}
+
+
+// errors which can point at where in the source they come from
+pub trait Located {
+    // most precise position known, that of the innermost error
+    fn pos(&self) -> Option<Pos>;
+
+    // other places worth pointing at
+    fn notes(&self) -> Vec<(&'static str, Pos)> {
+        Vec::new()
+    }
+}
+
+
+impl Located for LexingError {
+    fn pos(&self) -> Option<Pos> {
+        Some(LexingError::pos(self))
+    }
+}
+
+
+impl<LE: Located + Error + Clone> Located for ParsingError<LE> {
+    fn pos(&self) -> Option<Pos> {
+        match self {
+            Self::LexingError(err) => err.pos(),
+            Self::InvalidWord { word, .. } => Some(word.pos()),
+        }
+    }
+}
+
+
+impl<PE: Located + Error + Clone> Located for ProcessingError<PE> {
+    fn pos(&self) -> Option<Pos> {
+        match self {
+            Self::EarlyEoE => None,
+            Self::ParsingFailure(err) => err.pos(),
+            Self::InvalidElement { elem, info: InvalidElementInfo::IncludedCodeParsingFailure(err) } => err.pos().or(Some(elem.pos())),
+            Self::InvalidElement { elem, info: InvalidElementInfo::IncludedFileProcessingFailure(err) } => err.pos().or(Some(elem.pos())),
+            Self::InvalidElement { elem, .. } => Some(elem.pos()),
+        }
+    }
+}
+
+
+impl<PE: Located + Error + Clone> Located for AssemblingError<PE> {
+    fn pos(&self) -> Option<Pos> {
+        match self {
+            Self::ProcessingError(err) => err.pos(),
+            Self::InvalidInstruct { instruct, .. } => Some(instruct.pos()),
+            Self::Overlap { instruct, .. } | Self::ExceedsRam { instruct, .. } => Some(instruct.pos()),
+            Self::ProgTooLarge => None,
+        }
+    }
+
+    fn notes(&self) -> Vec<(&'static str, Pos)> {
+        match self {
+            Self::Overlap { other, .. } => vec![("overlapped code placed from here", other.pos())],
+            _ => Vec::new(),
+        }
+    }
+}
+
+
+impl Located for AssemblingWarning {
+    fn pos(&self) -> Option<Pos> {
+        match self {
+            Self::DuplicateLabel { label, .. } => Some(label.pos),
+        }
+    }
+
+    fn notes(&self) -> Vec<(&'static str, Pos)> {
+        match self {
+            Self::DuplicateLabel { first, .. } => vec![("first defined here", first.pos)],
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+
+// one error or warning: its innermost message with the source it points at,
+// the whole chain of messages when it points nowhere
+pub struct Report<'a, E: Error + Located> {
+    pub severity: Severity,
+    pub err: &'a E,
+    pub sources: &'a SourceMap,
+}
+
+
+impl<E: Error + Located> Display for Report<'_, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut innermost: &dyn Error = self.err;
+        while let Some(source) = innermost.source() {
+            innermost = source;
+        };
+
+        match self.err.pos() {
+            Some(pos) => {
+                writeln!(f, "{}: {innermost}", self.severity)?;
+                write!(f, "{}", Snippet { pos, sources: self.sources })?;
+            },
+            None => {
+                writeln!(f, "{}: {}", self.severity, self.err)?;
+                let mut err: &dyn Error = self.err;
+                while let Some(source) = err.source() {
+                    err = source;
+                    writeln!(f, "source of which: {err}")?;
+                };
+            },
+        };
+
+        for (note, pos) in self.err.notes() {
+            writeln!(f, "note: {note}")?;
+            write!(f, "{}", Snippet { pos, sources: self.sources })?;
+        };
+
+        Ok(())
+    }
+}
+
+
+// the source line an error points at with a caret under it, followed by the macros it was expanded from
+//
+//   --> lib/std/macros/inc.wts:3:13
+//    |
+//  3 |     set $ob @step
+//    |             ^
+//    = expanded from macro `inc` at progs/nums.wts:12:5
+pub struct Snippet<'a> {
+    pub pos: Pos,
+    pub sources: &'a SourceMap,
+}
+
+
+impl Display for Snippet<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let Self { pos, sources } = *self;
+        let number = (pos.line() + 1).to_string();
+        let gutter = " ".repeat(number.len());
+
+        writeln!(f, "{gutter}--> {}", sources.location(pos))?;
+        if let Some(line) = sources.line(pos) {
+            // tabs are kept so that the caret lines up
+            let indent = line.chars().take(pos.column()).map(|c| if c == '\t' { '\t' } else { ' ' }).collect::<String>();
+            writeln!(f, "{gutter} |")?;
+            writeln!(f, "{number} | {line}")?;
+            writeln!(f, "{gutter} | {indent}^")?;
+        };
+
+        let mut expansion = pos.expansion();
+        while let Some(exp) = sources.expansion(expansion) {
+            writeln!(f, "{gutter} = expanded from macro `{}` at {}", exp.name, sources.location(exp.call))?;
+            expansion = exp.call.expansion();
+        };
+
+        Ok(())
+    }
+}
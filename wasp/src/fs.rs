@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use normalize_path::NormalizePath;
+
+
+// where `!include`, `!lib` and `!file` read from
+pub trait FileSystem {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+
+// the actual filesystem
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFs;
+
+
+impl FileSystem for RealFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+}
+
+
+// files kept in memory, every path is taken as relative to `/` (so `lib/a.wts` is `/lib/a.wts`)
+#[derive(Debug, Clone, Default)]
+pub struct VirtualFs {
+    files: HashMap<PathBuf, Vec<u8>>,
+}
+
+
+impl VirtualFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_file(mut self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) -> Self {
+        self.add(path, contents);
+        self
+    }
+
+    pub fn add(&mut self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) {
+        self.files.insert(rooted(path.as_ref()), contents.into());
+    }
+}
+
+
+impl FileSystem for VirtualFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files.get(&rooted(path))
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no such file in virtual filesystem: {}", path.display())))
+    }
+}
+
+
+fn rooted(path: &Path) -> PathBuf {
+    Path::new("/").join(path).normalize()
+}
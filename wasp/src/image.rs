@@ -0,0 +1,196 @@
+use std::fmt::{Display, Formatter};
+use watto::Segment;
+
+// bytes per line (or record) of the text formats
+const LINE_BYTES: usize = 16;
+const ARRAY_LINE_BYTES: usize = 12;
+
+
+#[derive(Debug, Clone, Copy)]
+pub enum ImageFormat {
+    IntelHex,
+    SRecord,
+    Logisim,
+    Readmemh,
+    CArray,
+    RustArray,
+}
+
+
+// memory image of a program in a format other tools load, segments are kept apart at their addresses
+// (bss segments hold nothing so they are left out)
+pub struct Image<'a> {
+    pub format: ImageFormat,
+    pub entry: u16,
+    pub segments: &'a [Segment],
+}
+
+
+impl Image<'_> {
+    fn stored(&self) -> impl Iterator<Item = &Segment> {
+        self.segments.iter().filter(|seg| !seg.data.is_empty())
+    }
+
+    // every byte from address 0 up to the last stored one
+    fn flat(&self) -> Vec<u8> {
+        let mut image = vec![0; self.stored().map(|seg| seg.addr as usize + seg.data.len()).max().unwrap_or(0)];
+        for seg in self.stored() {
+            image[seg.addr as usize..][..seg.data.len()].copy_from_slice(&seg.data);
+        };
+        image
+    }
+
+    // chunks of a segment along with their addresses
+    fn records(seg: &Segment) -> impl Iterator<Item = (u16, &[u8])> {
+        seg.data.chunks(LINE_BYTES).enumerate().map(|(i, chunk)| (seg.addr.wrapping_add((i * LINE_BYTES) as u16), chunk))
+    }
+
+    // `:LLAAAATT<data>CC`, checksum being the two's complement of the sum of all the other bytes
+    fn intel_hex(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let record = |f: &mut Formatter<'_>, kind: u8, addr: u16, data: &[u8]| {
+            let mut bytes = vec![data.len() as u8];
+            bytes.extend(addr.to_be_bytes());
+            bytes.push(kind);
+            bytes.extend_from_slice(data);
+            let checksum = bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)).wrapping_neg();
+            writeln!(f, ":{}{checksum:0>2X}", hex(&bytes))
+        };
+
+        for seg in self.stored() {
+            for (addr, chunk) in Self::records(seg) {
+                record(f, 0x00, addr, chunk)?;
+            };
+        };
+        // start segment address (cs:ip), cs being always 0
+        let mut start = vec![0, 0];
+        start.extend(self.entry.to_be_bytes());
+        record(f, 0x03, 0x0000, &start)?;
+        record(f, 0x01, 0x0000, &[])
+    }
+
+    // `S<type><count><addr><data><checksum>`, checksum being the ones' complement of the sum of count, address and data
+    fn s_record(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let record = |f: &mut Formatter<'_>, kind: u8, addr: u16, data: &[u8]| {
+            let mut bytes = vec![(data.len() + 3) as u8];
+            bytes.extend(addr.to_be_bytes());
+            bytes.extend_from_slice(data);
+            let checksum = !bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+            writeln!(f, "S{kind}{}{checksum:0>2X}", hex(&bytes))
+        };
+
+        record(f, 0, 0x0000, b"wasp")?;
+        let mut count = 0u16;
+        for seg in self.stored() {
+            for (addr, chunk) in Self::records(seg) {
+                record(f, 1, addr, chunk)?;
+                count = count.wrapping_add(1);
+            };
+        };
+        record(f, 5, count, &[])?;
+        record(f, 9, self.entry, &[])
+    }
+
+    // logisim memory image, one byte per address from 0, longer runs of a value written as `count*value`
+    fn logisim(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "v2.0 raw")?;
+
+        let image = self.flat();
+        let mut words = Vec::new();
+        let mut rest = image.as_slice();
+        while let Some(&b) = rest.first() {
+            let run = rest.iter().take_while(|&&other| other == b).count();
+            if run >= 4 {
+                words.push(format!("{run}*{b:x}"));
+            } else {
+                words.extend((0..run).map(|_| format!("{b:x}")));
+            };
+            rest = &rest[run..];
+        };
+
+        for line in words.chunks(LINE_BYTES) {
+            writeln!(f, "{}", line.join(" "))?;
+        };
+        Ok(())
+    }
+
+    // verilog `$readmemh`, every segment starting with its address
+    fn readmemh(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "// entry: {:0>4x}", self.entry)?;
+        for seg in self.stored() {
+            writeln!(f, "@{:0>4x}", seg.addr)?;
+            for (_, chunk) in Self::records(seg) {
+                writeln!(f, "{}", chunk.iter().map(|b| format!("{b:0>2x}")).collect::<Vec<_>>().join(" "))?;
+            };
+        };
+        Ok(())
+    }
+
+    fn c_array(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "/* watto program image generated by wasp */")?;
+        writeln!(f, "#include <stdint.h>")?;
+        writeln!(f)?;
+        writeln!(f, "const uint16_t watto_entry = 0x{:0>4x};", self.entry)?;
+
+        for seg in self.stored() {
+            writeln!(f)?;
+            writeln!(f, "const uint8_t watto_segment_{:0>4x}[{}] = {{", seg.addr, seg.data.len())?;
+            array_body(f, &seg.data)?;
+            writeln!(f, "}};")?;
+        };
+
+        writeln!(f)?;
+        writeln!(f, "const struct {{ uint16_t addr; uint16_t len; const uint8_t *data; }} watto_segments[{}] = {{", self.stored().count())?;
+        for seg in self.stored() {
+            writeln!(f, "    {{ 0x{:0>4x}, {}, watto_segment_{:0>4x} }},", seg.addr, seg.data.len(), seg.addr)?;
+        };
+        writeln!(f, "}};")
+    }
+
+    fn rust_array(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "// watto program image generated by wasp")?;
+        writeln!(f)?;
+        writeln!(f, "pub const ENTRY: u16 = 0x{:0>4x};", self.entry)?;
+
+        for seg in self.stored() {
+            writeln!(f)?;
+            writeln!(f, "pub const SEGMENT_{:0>4X}: [u8; {}] = [", seg.addr, seg.data.len())?;
+            array_body(f, &seg.data)?;
+            writeln!(f, "];")?;
+        };
+
+        writeln!(f)?;
+        writeln!(f, "pub const SEGMENTS: [(u16, &[u8]); {}] = [", self.stored().count())?;
+        for seg in self.stored() {
+            writeln!(f, "    (0x{:0>4x}, &SEGMENT_{:0>4X}),", seg.addr, seg.addr)?;
+        };
+        writeln!(f, "];")
+    }
+}
+
+
+impl Display for Image<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.format {
+            ImageFormat::IntelHex => self.intel_hex(f),
+            ImageFormat::SRecord => self.s_record(f),
+            ImageFormat::Logisim => self.logisim(f),
+            ImageFormat::Readmemh => self.readmemh(f),
+            ImageFormat::CArray => self.c_array(f),
+            ImageFormat::RustArray => self.rust_array(f),
+        }
+    }
+}
+
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:0>2X}")).collect()
+}
+
+
+// `0x..` bytes separated by commas, indented
+fn array_body(f: &mut Formatter<'_>, data: &[u8]) -> std::fmt::Result {
+    for line in data.chunks(ARRAY_LINE_BYTES) {
+        writeln!(f, "    {},", line.iter().map(|b| format!("0x{b:0>2x}")).collect::<Vec<_>>().join(", "))?;
+    };
+    Ok(())
+}
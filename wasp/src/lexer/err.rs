@@ -27,6 +27,7 @@ pub enum LexingErrorInfo {
     PrefixDetached,
     UnclosedSurroundPair,
     EscapingVoid,
+    UnknownOperator,
 }
 
 
@@ -45,6 +46,7 @@ impl Display for LexingErrorInfo {
             Self::PrefixDetached => write!(f, "prefix before whitespace"),
             Self::UnclosedSurroundPair => write!(f, "prefix-suffix pair hasn't been closed"),
             Self::EscapingVoid => write!(f, "you can't escape void."),
+            Self::UnknownOperator => write!(f, "unknown operator (`<` and `>` only come as shifts `<<` and `>>`)"),
         }
     }
 }
@@ -1,4 +1,5 @@
 use std::str::Chars;
+use crate::diagnostic::collect_all;
 pub use err::{LexingError, LexingErrorInfo};
 pub use pos::Pos;
 pub use word::Word;
@@ -11,8 +12,9 @@ mod err;
 pub struct Lexer<C: Iterator<Item = char>> {
     chars: C,
     pos: Pos,
-    err: Option<LexingError>,
+    recovering: bool,  // after an error the rest of its line is skipped
     buf: Option<char>,
+    depth: usize,  // of parentheses, within them `/` is division instead of a comment
 }
 
 
@@ -21,16 +23,25 @@ impl<C: Iterator<Item = char>> Lexer<C> {
     pub const SURROUND_PAIRS: [(char, char); 1] = [('"', '"')];
     pub const ESCAPE: char = '\\';
     pub const COMMENT_PAIR: (char, char) = ('/', '\n');
+    // `<` and `>` only come doubled (shifts)
+    pub const OPERATORS: [char; 11] = ['+', '-', '*', '/', '&', '|', '^', '<', '>', '(', ')'];
     
     pub fn new(chars: C) -> Self {
         Self {
             chars,
             pos: Default::default(),
-            err: None,
+            recovering: false,
             buf: None,
+            depth: 0,
         }
     }
     
+    // positions of words refer to this file of the source map
+    pub fn with_file(mut self, file: usize) -> Self {
+        self.pos = Pos::in_file(file);
+        self
+    }
+    
     fn next_c(&mut self) -> Option<char> {
         let c = self.buf.take().or_else(|| self.chars.next())?;
         
@@ -43,17 +54,46 @@ impl<C: Iterator<Item = char>> Lexer<C> {
         Some(c)
     }
     
+    fn operator(&mut self, c: char, pos: Pos) -> Option<Result<Word, LexingError>> {
+        let value = match c {
+            '<' | '>' => match self.next_c() {
+                Some(next) if next == c => format!("{c}{c}"),
+                _ => { return self.err(LexingErrorInfo::UnknownOperator); },
+            },
+            '(' => {
+                self.depth += 1;
+                c.to_string()
+            },
+            ')' => {
+                self.depth = self.depth.saturating_sub(1);
+                c.to_string()
+            },
+            _ => c.to_string(),
+        };
+        
+        Some(Ok(Word { prefix: None, value, pos, suffix: None, operator: true }))
+    }
+    
     fn err(&mut self, err: LexingErrorInfo) -> Option<Result<Word, LexingError>> {
-        let full_err = LexingError { pos: self.pos, info: err }; 
-        self.err = Some(full_err.clone());
-        Some(Err(full_err))
+        self.recovering = true;
+        Some(Err(LexingError { pos: self.pos, info: err }))
+    }
+    
+    fn skip_line(&mut self) {
+        self.recovering = false;
+        self.depth = 0;
+        while let Some(c) = self.next_c() {
+            if c == '\n' {
+                break;
+            };
+        };
     }
 }
 
 impl Lexer<Chars<'_>> {
-    pub fn lex(s: &str) -> Result<Vec<Word>, LexingError> {
+    pub fn lex(s: &str) -> Result<Vec<Word>, Vec<LexingError>> {
         let mut chars = s.chars();
-        Lexer::new(&mut chars).try_collect()
+        collect_all(Lexer::new(&mut chars))
     }
 }
 
@@ -62,11 +102,11 @@ impl<C: Iterator<Item = char>> Iterator for Lexer<C> {
     type Item = Result<Word, LexingError>;
     
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(err) = self.err.clone() {
-            return Some(Err(err));
+        if self.recovering {
+            self.skip_line();
         };
         
-        let start_pos = self.pos;
+        let mut start_pos = self.pos;
         let mut prefix = None;
         let mut buf = String::new();
         let mut in_comment = false;
@@ -74,6 +114,11 @@ impl<C: Iterator<Item = char>> Iterator for Lexer<C> {
         let mut escaping = false;
         
         loop {
+            // a word starts at its first char, not at the whitespace or comment before it
+            if prefix.is_none() && buf.is_empty() && !escaping {
+                start_pos = self.pos;
+            };
+            
             match self.next_c() {
                 None => {
                     if in_surround.is_some() {
@@ -83,7 +128,7 @@ impl<C: Iterator<Item = char>> Iterator for Lexer<C> {
                     } else if prefix.is_some() && buf.is_empty() {
                         return self.err(LexingErrorInfo::PrefixAtEnd);
                     } else if !buf.is_empty() {
-                        return Some(Ok(Word { prefix, value: buf, pos: start_pos, suffix: None }));
+                        return Some(Ok(Word { prefix, value: buf, pos: start_pos, suffix: None, operator: false }));
                     } else {
                         return None;
                     };
@@ -99,7 +144,7 @@ impl<C: Iterator<Item = char>> Iterator for Lexer<C> {
                         escaping = true;
                     } else if let Some(end) = in_surround {
                         if c == end {
-                            return Some(Ok(Word { prefix, value: buf, pos: start_pos, suffix: Some(end) }));
+                            return Some(Ok(Word { prefix, value: buf, pos: start_pos, suffix: Some(end), operator: false }));
                         } else {
                             buf.push(c);
                         };
@@ -107,7 +152,12 @@ impl<C: Iterator<Item = char>> Iterator for Lexer<C> {
                         if c == Self::COMMENT_PAIR.1 {
                             in_comment = false;
                         };
-                    } else if c == Self::COMMENT_PAIR.0 {
+                    } else if c == Self::COMMENT_PAIR.0 && self.depth == 0 && !buf.is_empty() {
+                        // a comment ends the word before it, eg `set $si %loop/ forever`
+                        self.buf = Some(c);
+                        self.pos.prev_col();
+                        return Some(Ok(Word { prefix, value: buf, pos: start_pos, suffix: None, operator: false }));
+                    } else if c == Self::COMMENT_PAIR.0 && self.depth == 0 {
                         in_comment = true;
                     } else if let Some((start, end)) = Self::SURROUND_PAIRS.iter().copied().find(|(start, _)| *start == c) {
                         prefix = Some(start);
@@ -115,15 +165,27 @@ impl<C: Iterator<Item = char>> Iterator for Lexer<C> {
                     } else if Self::PREFIXES.contains(&c) {
                         if !buf.is_empty() {
                             self.buf = Some(c);
-                            return Some(Ok(Word { prefix, value: buf, pos: start_pos, suffix: None }));
+                            self.pos.prev_col();
+                            return Some(Ok(Word { prefix, value: buf, pos: start_pos, suffix: None, operator: false }));
                         } else if prefix.is_some() {
                             return self.err(LexingErrorInfo::MultiplePrefixesEncountered);
                         } else {
                             prefix = Some(c);
                         };
+                    } else if Self::OPERATORS.contains(&c) {
+                        if !buf.is_empty() {
+                            self.buf = Some(c);
+                            self.pos.prev_col();
+                            return Some(Ok(Word { prefix, value: buf, pos: start_pos, suffix: None, operator: false }));
+                        } else if prefix.is_some() {
+                            // part of the word, eg `~-2` or `'(`
+                            buf.push(c);
+                        } else {
+                            return self.operator(c, start_pos);
+                        };
                     } else if c.is_whitespace() {
                         if !buf.is_empty() {
-                            return Some(Ok(Word { prefix, value: buf, pos: start_pos, suffix: None }));
+                            return Some(Ok(Word { prefix, value: buf, pos: start_pos, suffix: None, operator: false }));
                         } else if prefix.is_some() {
                             return self.err(LexingErrorInfo::PrefixDetached);
                         };
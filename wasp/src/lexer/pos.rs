@@ -5,10 +5,28 @@ pub struct Pos {
     abs: usize,
     line: usize,
     column: usize,
+    file: usize,  // index into the source map
+    expansion: usize,  // macro expansion it was substituted into (0 for none), see the source map
 }
 
 
 impl Pos {
+    pub(super) fn in_file(file: usize) -> Self {
+        Self { file, ..Default::default() }
+    }
+    
+    pub fn file(self) -> usize {
+        self.file
+    }
+    
+    pub fn expansion(self) -> usize {
+        self.expansion
+    }
+    
+    pub fn with_expansion(self, expansion: usize) -> Self {
+        Self { expansion, ..self }
+    }
+    
     pub fn line(self) -> usize {
         self.line
     }
@@ -27,6 +45,12 @@ impl Pos {
         self.abs += 1;
         self.column += 1;
     }
+
+    // for a char which was read but put back
+    pub(super) fn prev_col(&mut self) {
+        self.abs -= 1;
+        self.column -= 1;
+    }
 }
 
 
@@ -7,6 +7,7 @@ pub struct Word {
     pub(super) prefix: Option<char>,
     pub(super) suffix: Option<char>,
     pub(super) value: String,
+    pub(super) operator: bool,
 }
 
 
@@ -23,6 +24,10 @@ impl Word {
         self.suffix
     }
     
+    pub fn is_operator(&self) -> bool {
+        self.operator
+    }
+    
     pub fn value(&self) -> &str {
         &self.value
     }
@@ -0,0 +1,22 @@
+#![feature(iterator_try_collect)]
+#![feature(ascii_char)]
+#![feature(let_chains)]
+#![feature(string_into_chars)]
+
+// the assembler as a library: `assemble` runs the whole pipeline on a source,
+// the stages themselves (lexer, parser, processor, assembler) are usable on their own as well
+
+pub mod lexer;
+pub mod parser;
+pub mod processor;
+pub mod assembler;
+pub mod listing;
+pub mod diagnostic;
+pub mod image;
+pub mod fs;
+mod pipeline;
+
+pub use assembler::Program;
+pub use diagnostic::SourceMap;
+pub use fs::{FileSystem, RealFs, VirtualFs};
+pub use pipeline::{assemble, AsmError, Assembled, Diagnostics, Options};
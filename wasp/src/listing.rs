@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use crate::assembler::{Placement, Program};
+
+// at most this many bytes are shown per row, longer data is cut off with `..`
+const LISTED_BYTES: usize = 4;
+const BYTES_WIDTH: usize = LISTED_BYTES * 3 + 2;
+
+
+fn file_name(file: Option<&PathBuf>) -> String {
+    file.map_or_else(|| String::from("-"), |path| path.display().to_string())
+}
+
+
+// label -> address, with the file and position it was defined at
+pub struct SymbolMap<'a>(pub &'a Program);
+
+
+impl Display for SymbolMap<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let labels = self.0.placements.iter()
+            .flat_map(|placement| placement.instruct.labels().iter().map(move |label| (placement, label)))
+            .collect::<Vec<_>>();
+        let width = labels.iter().map(|(_, label)| label.name.len()).max().unwrap_or(0);
+
+        for (placement, label) in labels {
+            let instruct = &placement.instruct;
+            // labels from macros are reported at the call site
+            let pos = if instruct.expansion().is_empty() { label.pos } else { instruct.site() };
+            writeln!(f, "{:0>4x}  {:<width$}  {}{pos}", placement.addr, label.name, file_name(instruct.file()))?;
+        };
+
+        Ok(())
+    }
+}
+
+
+// every placed instruct next to the source line it came from,
+// instructs expanded from macros are listed indented under the line calling the macro
+pub struct Listing<'a> {
+    program: &'a Program,
+    sources: HashMap<Option<PathBuf>, Vec<String>>,
+}
+
+
+impl<'a> Listing<'a> {
+    pub fn new(program: &'a Program, main_file: Option<&Path>, main_source: &str) -> Self {
+        let mut sources = HashMap::new();
+        sources.insert(main_file.map(Path::to_path_buf), main_source.lines().map(String::from).collect());
+
+        for placement in program.placements.iter() {
+            sources.entry(placement.instruct.file().cloned()).or_insert_with_key(|file: &Option<PathBuf>| {
+                file.as_ref()
+                    .and_then(|path| std::fs::read_to_string(path).ok())
+                    .map(|source| source.lines().map(String::from).collect())
+                    .unwrap_or_default()
+            });
+        };
+
+        Self { program, sources }
+    }
+
+    fn source_line(&self, file: Option<&PathBuf>, line: usize) -> &str {
+        self.sources.get(&file.cloned())
+            .and_then(|lines| lines.get(line))
+            .map_or("", |line| line.trim_end())
+    }
+
+    fn bytes(&self, placement: &Placement) -> String {
+        let bytes = self.program.placed_bytes(placement);
+        let mut s = bytes.iter().take(LISTED_BYTES).map(|b| format!("{b:0>2x}")).collect::<Vec<_>>().join(" ");
+        if bytes.len() > LISTED_BYTES {
+            s.push_str(" ..");
+        };
+        s
+    }
+}
+
+
+impl Display for Listing<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut last_file = None;
+        let mut last_site = None;
+
+        for placement in self.program.placements.iter() {
+            let instruct = &placement.instruct;
+            let addr = placement.addr;
+
+            let file = instruct.file();
+            if last_file != Some(file) {
+                if last_file.is_some() {
+                    writeln!(f)?;
+                };
+                writeln!(f, "/ {}", file_name(file))?;
+                last_file = Some(file);
+                last_site = None;
+            };
+
+            for label in instruct.labels() {
+                writeln!(f, "{addr:0>4x}  {:<BYTES_WIDTH$}       :{}", "", label.name)?;
+            };
+
+            let site = instruct.site().line();
+            let is_new_site = last_site != Some(site);
+            last_site = Some(site);
+
+            let source = self.source_line(file, site);
+            let depth = instruct.expansion().len();
+            if depth == 0 && is_new_site {
+                writeln!(f, "{addr:0>4x}  {:<BYTES_WIDTH$}{:>5}  {source}", self.bytes(placement), site + 1)?;
+            } else {
+                if is_new_site {
+                    writeln!(f, "      {:<BYTES_WIDTH$}{:>5}  {source}", "", site + 1)?;
+                };
+                writeln!(f, "{addr:0>4x}  {:<BYTES_WIDTH$}       {}{}", self.bytes(placement), "    ".repeat(depth), instruct.operation())?;
+            };
+        };
+
+        Ok(())
+    }
+}
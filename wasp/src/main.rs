@@ -1,21 +1,21 @@
-#![feature(iterator_try_collect)]
-#![feature(ascii_char)]
-#![feature(let_chains)]
-#![feature(string_into_chars)]
-
+use std::cell::RefCell;
 use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::io::{Read, Write};
+use std::rc::Rc;
 use clap::Parser;
+use watto::{Disassembly, Executable, IsaReference, Object};
+use wasp::assembler::AssemblingWarning;
+use wasp::diagnostic::{Located, Report, Severity, SourceMap};
+use wasp::image::{Image, ImageFormat};
+use wasp::lexer::Lexer;
+use wasp::listing::{Listing, SymbolMap};
+use wasp::parser;
+use wasp::processor::{Defines, Processor, ProcessingShortcutError, PseudoReference};
+use wasp::{Assembled, Diagnostics, Options};
 use crate::argparser::Format;
-use crate::assembler::Assembler;
-use crate::lexer::Lexer;
-use crate::processor::Processor;
 
 mod argparser;
-mod lexer;
-mod parser;
-mod processor;
-mod assembler;
 
 fn handle_error(context: &'static str, mut err: &dyn Error) -> ! {
     eprintln!("while {context}, an error occurred: {err}");
@@ -28,10 +28,73 @@ fn handle_error(context: &'static str, mut err: &dyn Error) -> ! {
     std::process::exit(1)
 }
 
+fn report_warnings(warnings: &[AssemblingWarning], sources: &SourceMap) {
+    for warning in warnings {
+        eprintln!("{}", Report { severity: Severity::Warning, err: warning, sources });
+    };
+}
+
+// every error with the source it points at
+fn handle_source_errors(context: &'static str, errs: &[impl Error + Located], sources: &SourceMap) -> ! {
+    for err in errs {
+        eprintln!("{}", Report { severity: Severity::Error, err, sources });
+    };
+    eprintln!("while {context}, {} error(s) occurred", errs.len());
+    
+    std::process::exit(1)
+}
+
+#[derive(Debug)]
+struct UnknownEntryError(String);
+
+impl Display for UnknownEntryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no such label: {}", self.0)
+    }
+}
+
+impl Error for UnknownEntryError {}
+
+
 fn main() {
     let args: argparser::AsmArgs = argparser::AsmArgs::parse();
 
     let rel_path = args.source.is_local().then(|| args.source.parent().unwrap().to_path_buf());
+    let source_file = args.source.is_local().then(|| args.source.to_path_buf());
+    let defines = Defines {
+        features: args.features.iter().cloned().collect(),
+        env: args.defines.iter().cloned().collect(),
+    };
+    
+    if let Format::Isa = args.format {
+        let mut out = args.out.create().unwrap_or_else(|err| handle_error("creating output stream", &err));
+
+        if !args.dry {
+            write!(out, "{IsaReference}\n{PseudoReference}").unwrap_or_else(|err| handle_error("writing to output", &err));
+        };
+        
+        return;
+    };
+    
+    if let Format::Disasm = args.format {
+        let bytes = args.source.read_all().unwrap_or_else(|err| handle_error("reading binary", &err)).into_vec();
+        
+        let disasm = if Executable::is_executable(&bytes) {
+            Disassembly::from_executable(&Executable::decode(&bytes).unwrap_or_else(|err| handle_error("decoding executable", &err)))
+        } else if Object::is_object(&bytes) {
+            Disassembly::from_raw(&Object::decode(&bytes).unwrap_or_else(|err| handle_error("decoding object", &err)).code)
+        } else {
+            Disassembly::from_raw(&bytes)
+        };
+
+        let mut out = args.out.create().unwrap_or_else(|err| handle_error("creating output stream", &err));
+
+        if !args.dry {
+            write!(out, "{disasm}").unwrap_or_else(|err| handle_error("writing to output", &err));
+        };
+        
+        return;
+    };
     
     let source = {
         let mut buf = String::new();
@@ -39,21 +102,92 @@ fn main() {
         buf
     };
     
+    // the assembling pipeline keeps its own
+    let mut sources = SourceMap::default();
+    sources.add_path(source_file.as_ref(), source.clone());
+    
     match args.format {
-        Format::Binary => {
-            let prog = 
-                Assembler::new(Processor::new(parser::Parser::new(Lexer::new(source.chars())), args.lib_path.map(|p| p.to_path_buf()), rel_path, !args.forbid_abs_includes).unwrap_or_else(|err| handle_error("initializing processor", &err)))
-                .assemble()
-                .unwrap_or_else(|err| handle_error("assembling program", &err));
+        Format::Binary | Format::Executable | Format::Object
+        | Format::IntelHex | Format::Srec | Format::Logisim | Format::Readmemh | Format::CArray | Format::RustArray => {
+            let mut options = Options::new()
+                .allow_abs_includes(!args.forbid_abs_includes)
+                .defines(defines)
+                .relocatable(matches!(args.format, Format::Object));
+            if let Some(path) = &source_file {
+                options = options.file(path);
+            };
+            if let Some(path) = &args.lib_path {
+                options = options.lib_path(path.to_path_buf());
+            };
+            if let Some(ram) = args.ram {
+                options = options.ram(ram);
+            };
+
+            let Assembled { program: prog, sources } = wasp::assemble(&source, &options).unwrap_or_else(|diags| {
+                let Diagnostics { errors, warnings, sources, setup } = diags;
+                if let Some(err) = setup {
+                    handle_error("initializing processor", &err);
+                };
+                report_warnings(&warnings, &sources);
+                handle_source_errors("assembling program", &errors, &sources)
+            });
+            report_warnings(&prog.warnings, &sources);
+            
+            if let Some(path) = &args.symbols {
+                let mut out = path.clone().create().unwrap_or_else(|err| handle_error("creating symbol map", &err));
+                
+                if !args.dry {
+                    write!(out, "{}", SymbolMap(&prog)).unwrap_or_else(|err| handle_error("writing symbol map", &err));
+                };
+            };
+            
+            if let Some(path) = &args.listing {
+                let mut out = path.clone().create().unwrap_or_else(|err| handle_error("creating listing", &err));
+                
+                if !args.dry {
+                    write!(out, "{}", Listing::new(&prog, source_file.as_deref(), &source)).unwrap_or_else(|err| handle_error("writing listing", &err));
+                };
+            };
+
+            let entry = match &args.entry {
+                Some(label) => prog.symbol(label).unwrap_or_else(|| handle_error("resolving entry point", &UnknownEntryError(label.clone()))),
+                None => 0x0000,
+            };
+            let image = |format| Image { format, entry, segments: &prog.segments }.to_string().into_bytes();
+
+            let bytes = match args.format {
+                Format::Executable => {
+                    let mut exe = Executable::new(entry, prog.segments.clone());
+                    if !args.strip {
+                        exe.symbols = Some(prog.symbols.clone());
+                    };
+                    exe.encode()
+                },
+                Format::Object => {
+                    let mut obj = prog.object();
+                    if args.strip {
+                        obj.symbols = None;
+                    };
+                    obj.encode()
+                },
+                Format::IntelHex => image(ImageFormat::IntelHex),
+                Format::Srec => image(ImageFormat::SRecord),
+                Format::Logisim => image(ImageFormat::Logisim),
+                Format::Readmemh => image(ImageFormat::Readmemh),
+                Format::CArray => image(ImageFormat::CArray),
+                Format::RustArray => image(ImageFormat::RustArray),
+                Format::Binary => prog.image(),
+                Format::Words | Format::Elements | Format::Instructs | Format::Disasm | Format::Isa => unreachable!(),
+            };
 
-            let mut out = args.out.create_with_len(prog.len() as u64).unwrap_or_else(|err| handle_error("creating output stream", &err));
+            let mut out = args.out.create_with_len(bytes.len() as u64).unwrap_or_else(|err| handle_error("creating output stream", &err));
 
             if !args.dry {
-                out.write_all(&prog).unwrap_or_else(|err| handle_error("writing to output", &err));
+                out.write_all(&bytes).unwrap_or_else(|err| handle_error("writing to output", &err));
             };
         },
         Format::Words => {
-            let words = Lexer::lex(&source).unwrap_or_else(|err| handle_error("lexing program", &err));
+            let words = Lexer::lex(&source).unwrap_or_else(|err| handle_source_errors("lexing program", &err, &sources));
             
             let mut out = args.out.create().unwrap_or_else(|err| handle_error("creating output stream", &err));
 
@@ -64,7 +198,7 @@ fn main() {
             };
         },
         Format::Elements => {
-            let elements = parser::Parser::parse(&source).unwrap_or_else(|err| handle_error("parsing program", &err));
+            let elements = parser::Parser::parse(&source).unwrap_or_else(|err| handle_source_errors("parsing program", &err, &sources));
 
             let mut out = args.out.create().unwrap_or_else(|err| handle_error("creating output stream", &err));
 
@@ -75,7 +209,11 @@ fn main() {
             };
         },
         Format::Instructs => {
-            let instructs = Processor::process_custom(&source, args.lib_path.map(|p| p.to_path_buf()), rel_path, !args.forbid_abs_includes).unwrap_or_else(|err| handle_error("processing program", &err));
+            let sources = Rc::new(RefCell::new(sources));
+            let instructs = Processor::process_custom(&source, args.lib_path.map(|p| p.to_path_buf()), rel_path, !args.forbid_abs_includes, defines, sources.clone()).unwrap_or_else(|err| match err {
+                ProcessingShortcutError::ProcessingErrors(errs) => handle_source_errors("processing program", &errs, &sources.borrow()),
+                err => handle_error("processing program", &err),
+            });
 
             let mut out = args.out.create().unwrap_or_else(|err| handle_error("creating output stream", &err));
 
@@ -85,6 +223,7 @@ fn main() {
                 };
             };
         },
+        Format::Disasm | Format::Isa => unreachable!(),
     }
 
     
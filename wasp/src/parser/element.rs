@@ -37,7 +37,61 @@ pub enum ElementValue {
     Literal(LiteralValue),
     Register(Register),
     Reference(i16),
-    Substitute(usize),
+    Substitute(String),
+    Operator(Operator),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+    LeftParen,
+    RightParen,
+}
+
+
+impl Operator {
+    // binding strength as a binary operator (higher binds tighter), none for parentheses
+    pub fn precedence(self) -> Option<u8> {
+        match self {
+            Self::Or => Some(0),
+            Self::Xor => Some(1),
+            Self::And => Some(2),
+            Self::Shl | Self::Shr => Some(3),
+            Self::Add | Self::Sub => Some(4),
+            Self::Mul | Self::Div => Some(5),
+            Self::LeftParen | Self::RightParen => None,
+        }
+    }
+}
+
+
+impl TryFrom<&str> for Operator {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "+" => Ok(Self::Add),
+            "-" => Ok(Self::Sub),
+            "*" => Ok(Self::Mul),
+            "/" => Ok(Self::Div),
+            "&" => Ok(Self::And),
+            "|" => Ok(Self::Or),
+            "^" => Ok(Self::Xor),
+            "<<" => Ok(Self::Shl),
+            ">>" => Ok(Self::Shr),
+            "(" => Ok(Self::LeftParen),
+            ")" => Ok(Self::RightParen),
+            _ => Err(()),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -63,7 +117,8 @@ impl Display for ElementValue {
             Self::Reference(delta) => write!(f, "~{delta}"),
             Self::Literal(lit) => write!(f, "{lit}"),
             Self::Register(reg) => write!(f, "${reg}"),
-            Self::Substitute(i) => write!(f, "@{i}"),
+            Self::Substitute(name) => write!(f, "@{name}"),
+            Self::Operator(op) => write!(f, "{op}"),
         }
     }
 }
@@ -77,3 +132,21 @@ impl Display for LiteralValue {
         }
     }
 }
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Add => write!(f, "+"),
+            Self::Sub => write!(f, "-"),
+            Self::Mul => write!(f, "*"),
+            Self::Div => write!(f, "/"),
+            Self::And => write!(f, "&"),
+            Self::Or => write!(f, "|"),
+            Self::Xor => write!(f, "^"),
+            Self::Shl => write!(f, "<<"),
+            Self::Shr => write!(f, ">>"),
+            Self::LeftParen => write!(f, "("),
+            Self::RightParen => write!(f, ")"),
+        }
+    }
+}
@@ -28,8 +28,7 @@ impl<LE: Error + Clone> Error for ParsingError<LE> {
             Self::LexingError(err) => Some(err),
             Self::InvalidWord { 
                 info: InvalidWordInfo::LiteralInteger(err)
-                    | InvalidWordInfo::Reference(err)
-                    | InvalidWordInfo::Substitute(err), .. 
+                    | InvalidWordInfo::Reference(err), .. 
             } => Some(err),
             _ => None,
         }
@@ -45,7 +44,8 @@ pub enum InvalidWordInfo {
     IntegerRadix,
     Char,
     Reference(ParseIntError),
-    Substitute(ParseIntError),
+    Substitute,
+    Operator,
 }
 
 impl Display for InvalidWordInfo {
@@ -57,7 +57,8 @@ impl Display for InvalidWordInfo {
             Self::Reference(_) => write!(f, "invalid reference"),
             Self::Char => write!(f, "char word must be 1-char long"),
             Self::IntegerRadix => write!(f, "integer: radix"),
-            Self::Substitute(_) => write!(f, "invalid substitute index"),
+            Self::Substitute => write!(f, "substitute must be a param name or index"),
+            Self::Operator => write!(f, "unknown operator"),
         }
     }
 }
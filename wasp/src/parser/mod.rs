@@ -2,8 +2,9 @@ use std::error::Error;
 use std::str::{Chars, FromStr};
 use watto::Register;
 use crate::lexer::{Lexer, LexingError, Word};
+use crate::diagnostic::collect_all;
 
-pub use element::{Element, LiteralValue, ElementValue};
+pub use element::{Element, LiteralValue, ElementValue, Operator};
 pub use err::{InvalidWordInfo, ParsingError};
 
 mod element;
@@ -15,7 +16,6 @@ pub struct Parser<L, LE>
         LE: Error + Clone + 'static,
 {
     lexer: L,
-    err: Option<ParsingError<LE>>,
 }
 
 impl<L, LE> Parser<L, LE>
@@ -24,11 +24,11 @@ impl<L, LE> Parser<L, LE>
         LE: Error + Clone + 'static,
 {
     pub fn new(lexer: L) -> Self {
-        Self { lexer, err: None }
+        Self { lexer }
     }
 
+    // words are independent of each other, so parsing simply goes on with the next one
     fn err(&mut self, err: ParsingError<LE>) -> Option<Result<Element, ParsingError<LE>>> {
-        self.err = Some(err.clone());
         Some(Err(err))
     }
 }
@@ -42,13 +42,29 @@ impl<L, LE> Iterator for Parser<L, LE>
     type Item = Result<Element, ParsingError<LE>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(err) = self.err.clone() {
-            return Some(Err(err));
-        };
-
         Some(match self.lexer.next()? {
+            Ok(word) if word.is_operator() => {
+                match Operator::try_from(word.value()) {
+                    Ok(op) => Ok(Element { pos: word.pos(), value: ElementValue::Operator(op) }),
+                    Err(()) => { return self.err(ParsingError::InvalidWord { word, info: InvalidWordInfo::Operator }); },
+                }
+            },
             Ok(word) =>
                 Ok(match (word.prefix(), word.suffix()) {
+                    // bare numbers (decimal or 0x/0o/0b), mostly for use within expressions
+                    (None, None) if word.value().starts_with(|c: char| c.is_ascii_digit()) => {
+                        let (radix, digits) = match word.value().get(..2) {
+                            Some("0x") => (16, &word.value()[2..]),
+                            Some("0o") => (8, &word.value()[2..]),
+                            Some("0b") => (2, &word.value()[2..]),
+                            _ => (10, word.value()),
+                        };
+
+                        match u16::from_str_radix(digits, radix) {
+                            Ok(val) => Element { pos: word.pos(), value: ElementValue::Literal(LiteralValue::Number(val)) },
+                            Err(err) => { return self.err(ParsingError::InvalidWord { word, info: InvalidWordInfo::LiteralInteger(err) }); }
+                        }
+                    },
                     (None, None) => Element { pos: word.pos(), value: ElementValue::CpuInstruction(word.into_value()) },
                     (Some('!'), None) => Element { pos: word.pos(), value: ElementValue::ProcessorInstruction(word.into_value()) },
                     (Some('%'), None) => Element { pos: word.pos(), value: ElementValue::Variable(word.into_value()) },
@@ -88,9 +104,10 @@ impl<L, LE> Iterator for Parser<L, LE>
                     },
                     (Some('"'), Some('"')) => Element { pos: word.pos(), value: ElementValue::Literal(LiteralValue::String(word.into_value())) },
                     (Some('@'), None) => {
-                        match word.value().parse::<usize>() {
-                            Ok(i) => Element { pos: word.pos(), value: ElementValue::Substitute(i) },
-                            Err(err) => { return self.err(ParsingError::InvalidWord { word, info: InvalidWordInfo::Substitute(err) }); },
+                        if word.value().chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                            Element { pos: word.pos(), value: ElementValue::Substitute(word.into_value()) }
+                        } else {
+                            return self.err(ParsingError::InvalidWord { word, info: InvalidWordInfo::Substitute });
                         }
                     },
                     (_, _) => { return self.err(ParsingError::InvalidWord { word, info: InvalidWordInfo::SurroundPair }); }
@@ -102,7 +119,7 @@ impl<L, LE> Iterator for Parser<L, LE>
 
 
 impl Parser<Lexer<Chars<'_>>, LexingError> {
-    pub fn parse(s: &str) -> Result<Vec<Element>, ParsingError<LexingError>> {
-        Parser::new(Lexer::new(s.chars())).try_collect()
+    pub fn parse(s: &str) -> Result<Vec<Element>, Vec<ParsingError<LexingError>>> {
+        collect_all(Parser::new(Lexer::new(s.chars())))
     }
 }
@@ -0,0 +1,211 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use watto::Symbol;
+use crate::assembler::{Assembler, AssemblingError, AssemblingErrors, AssemblingWarning, Program};
+use crate::diagnostic::{Report, Severity, SourceMap};
+use crate::fs::{FileSystem, RealFs};
+use crate::lexer::{Lexer, LexingError};
+use crate::parser::{Parser, ParsingError};
+use crate::processor::{Defines, ProcessingError, Processor, ProcessorInitializationError};
+
+
+// any error a source can have once it gets to be processed
+pub type AsmError = AssemblingError<ProcessingError<ParsingError<LexingError>>>;
+
+
+// how a source gets assembled, see `assemble`
+#[derive(Clone)]
+pub struct Options {
+    file: Option<PathBuf>,
+    lib_path: Option<PathBuf>,
+    rel_path: Option<PathBuf>,
+    allow_abs_includes: bool,
+    defines: Defines,
+    relocatable: bool,
+    ram: Option<u16>,
+    fs: Rc<dyn FileSystem>,
+    virtual_fs: bool,
+}
+
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl Options {
+    pub fn new() -> Self {
+        Self { file: None, lib_path: None, rel_path: None, allow_abs_includes: true, defines: Defines::default(), relocatable: false, ram: None, fs: Rc::new(RealFs), virtual_fs: false }
+    }
+
+    // path the source was read from, errors point into it and `!include` is relative to its directory
+    // (unless given a relative path itself)
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.file = Some(path.into());
+        self
+    }
+
+    // directory `!lib` is relative to
+    pub fn lib_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.lib_path = Some(path.into());
+        self
+    }
+
+    // directory `!include` and `!file` are relative to
+    pub fn rel_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.rel_path = Some(path.into());
+        self
+    }
+
+    pub fn allow_abs_includes(mut self, allow: bool) -> Self {
+        self.allow_abs_includes = allow;
+        self
+    }
+
+    // enables a feature for `!iffeat`
+    pub fn feature(mut self, name: impl Into<String>) -> Self {
+        self.defines.features.insert(name.into());
+        self
+    }
+
+    // sets a value for `!ifenv`
+    pub fn define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.defines.env.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn defines(mut self, defines: Defines) -> Self {
+        self.defines = defines;
+        self
+    }
+
+    // assembles for an object, see `Program::object`
+    pub fn relocatable(mut self, relocatable: bool) -> Self {
+        self.relocatable = relocatable;
+        self
+    }
+
+    // everything placed has to fit below this address
+    pub fn ram(mut self, size: u16) -> Self {
+        self.ram = Some(size);
+        self
+    }
+
+    // where included files are read from instead of the actual filesystem,
+    // relative paths (of the file and the roots) are taken as relative to `/` of it, which `!include` is relative to unless told otherwise
+    pub fn fs(mut self, fs: impl FileSystem + 'static) -> Self {
+        self.fs = Rc::new(fs);
+        self.virtual_fs = true;
+        self
+    }
+
+    // roots of `!lib` and `!include` paths, in a virtual filesystem rooted at its `/`
+    // so that they are never resolved against the working directory
+    fn roots(&self) -> (Option<PathBuf>, Option<PathBuf>) {
+        let rel_path = self.rel_path.clone()
+            .or_else(|| self.file.as_ref().and_then(|file| file.parent()).map(Path::to_path_buf));
+
+        if !self.virtual_fs {
+            return (self.lib_path.clone(), rel_path);
+        };
+
+        let root = |path: PathBuf| Path::new("/").join(path);
+        (self.lib_path.clone().map(root), Some(rel_path.map_or_else(|| PathBuf::from("/"), root)))
+    }
+}
+
+
+pub struct Assembled {
+    pub program: Program,
+    pub sources: SourceMap,  // every file and macro expansion positions in the program can refer to
+}
+
+
+impl Assembled {
+    // memory from address 0 up to the last stored byte
+    pub fn bytes(&self) -> Vec<u8> {
+        self.program.image()
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.program.symbols
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<u16> {
+        self.program.symbol(name)
+    }
+}
+
+
+// everything wrong with a source, displayed the way wasp reports it
+pub struct Diagnostics {
+    pub errors: Vec<AsmError>,
+    pub warnings: Vec<AssemblingWarning>,
+    pub sources: SourceMap,
+    pub setup: Option<ProcessorInitializationError>,  // paths in the options are unusable, nothing got assembled
+}
+
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(err) = &self.setup {
+            return writeln!(f, "error: {err}");
+        };
+
+        for warning in self.warnings.iter() {
+            writeln!(f, "{}", Report { severity: Severity::Warning, err: warning, sources: &self.sources })?;
+        };
+        for err in self.errors.iter() {
+            writeln!(f, "{}", Report { severity: Severity::Error, err, sources: &self.sources })?;
+        };
+        write!(f, "{} error(s) occurred", self.errors.len())
+    }
+}
+
+
+impl std::fmt::Debug for Diagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+
+impl Error for Diagnostics {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.setup.as_ref().map(|err| err as &(dyn Error + 'static))
+    }
+}
+
+
+// runs the whole pipeline (lexer, parser, processor and assembler) on a source
+pub fn assemble(source: &str, options: &Options) -> Result<Assembled, Diagnostics> {
+    let sources = Rc::new(RefCell::new(SourceMap::default().with_virtual_root(options.virtual_fs)));
+    sources.borrow_mut().add_path(options.file.as_ref(), source.to_string());
+
+    let (lib_path, rel_path) = options.roots();
+    let processor = match Processor::new(Parser::new(Lexer::new(source.chars())), lib_path, rel_path, options.allow_abs_includes) {
+        Ok(processor) => processor,
+        Err(err) => { return Err(Diagnostics { errors: Vec::new(), warnings: Vec::new(), sources: sources.take(), setup: Some(err) }); },
+    };
+    let processor = processor
+        .with_file(options.file.clone())
+        .with_defines(options.defines.clone())
+        .with_sources(sources.clone())
+        .with_fs(options.fs.clone());
+
+    let res = Assembler::new(processor)
+        .with_relocations(options.relocatable)
+        .with_ram(options.ram)
+        .assemble();
+    let sources = Rc::unwrap_or_clone(sources).into_inner();
+
+    match res {
+        Ok(program) => Ok(Assembled { program, sources }),
+        Err(AssemblingErrors { errors, warnings }) => Err(Diagnostics { errors, warnings, sources, setup: None }),
+    }
+}
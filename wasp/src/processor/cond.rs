@@ -0,0 +1,19 @@
+use std::collections::{HashMap, HashSet};
+use crate::parser::Element;
+
+
+// what `!iffeat` and `!ifenv` are checked against
+#[derive(Debug, Clone, Default)]
+pub struct Defines {
+    pub features: HashSet<String>,
+    pub env: HashMap<String, String>,
+}
+
+
+// an open `!iffeat`/`!ifenv` block
+pub(super) struct Cond {
+    pub active: bool,
+    pub parent_active: bool,
+    pub has_else: bool,
+    pub opened_by: Element,
+}
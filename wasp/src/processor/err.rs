@@ -2,6 +2,7 @@ use std::error::Error;
 use std::fmt::{Display, Formatter};
 use crate::lexer::LexingError;
 use crate::parser::{Element, ParsingError};
+use super::ArgKind;
 
 #[derive(Debug, Clone)]
 pub enum ProcessingError<PE>
@@ -37,11 +38,24 @@ pub enum InvalidElementInfo
     CpuInstructionArg {
         expected: watto::Argument
     },
+    MissingCpuInstructionArg {
+        expected: watto::Argument
+    },
     ProcessorInstructArg,  // todo more info
+    PseudoArg,
     NonAsciiCharAsArg,
-    IncludedCodeParsingFailure(ParsingError<LexingError>),
+    IncludedCodeParsingFailure(Box<ParsingError<LexingError>>),
     IncludedFileProcessingFailure(Box<ProcessingError<ParsingError<LexingError>>>),
     MacroName,
+    MacroParam,
+    MacroArgKind {
+        expected: ArgKind
+    },
+    UnmatchedBlock,
+    UnclosedBlock,
+    UnclosedParenthesis,
+    UnmatchedConditional,
+    UnclosedConditional,
     AbsolutePathsForbidden,
     NoRelPathGiven,
     NoLibPathGiven,
@@ -71,10 +85,19 @@ impl Display for InvalidElementInfo {
             Self::CpuInstructionName => write!(f, "unknown cpu instruction name"),
             Self::ProcessorInstructName => write!(f, "unknown processor instruct name"),
             Self::CpuInstructionArg { expected } => write!(f, "expected {expected} as an arg"),
+            Self::MissingCpuInstructionArg { expected } => write!(f, "expected {expected} as an arg before the end of the line"),
             Self::ProcessorInstructArg => write!(f, "expected other process instruct arg"),
+            Self::PseudoArg => write!(f, "pseudo-instruction can't be expanded with this arg (see `--format isa`)"),
             Self::NonAsciiCharAsArg => write!(f, "can't encode non-ascii char as byte"),
             Self::IncludedCodeParsingFailure(_) => write!(f, "an error while parsing included code"),
             Self::MacroName => write!(f, "unknown macro name"),
+            Self::MacroParam => write!(f, "macro has no such param"),
+            Self::MacroArgKind { expected } => write!(f, "expected {expected} as a macro arg"),
+            Self::UnmatchedBlock => write!(f, "no open `!macro` or `!for` block to end"),
+            Self::UnclosedBlock => write!(f, "block is never closed with `!endmacro` or `!endfor`"),
+            Self::UnclosedParenthesis => write!(f, "expected `)` to close the expression"),
+            Self::UnmatchedConditional => write!(f, "no open `!iffeat` or `!ifenv` (or it already has an `!else`)"),
+            Self::UnclosedConditional => write!(f, "conditional block is never closed with `!endif`"),
             Self::IncludedFileProcessingFailure(_) => write!(f, "an error while processing included file"),
             Self::FailedToReadFile { reason } => write!(f, "failed to read file: {reason}"),
             Self::AbsolutePathsForbidden => write!(f, "absolute paths are disabled"),
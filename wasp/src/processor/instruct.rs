@@ -1,13 +1,19 @@
 use std::ffi::CString;
 use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::rc::Rc;
 use watto::{InstructionId, Register};
 use crate::lexer::Pos;
+use crate::parser::Operator;
+use super::Pseudo;
 
 #[derive(Debug, Clone)]
 pub struct Instruct {
     pub(super) pos: Pos,
-    pub(super) labels: Vec<String>,
+    pub(super) labels: Vec<Label>,
     pub(super) operation: Op,
+    pub(super) file: Option<Rc<PathBuf>>,  // none for source given on stdin
+    pub(super) expansion: Vec<Pos>,  // call sites of the macros it was expanded from, outermost first
 }
 
 impl Display for Instruct {
@@ -15,7 +21,7 @@ impl Display for Instruct {
         write!(f, "{} {}", self.pos, self.operation)?;
         
         if !self.labels.is_empty() {
-            write!(f, " (: {}) ", self.labels.join(" "))?;
+            write!(f, " (: {}) ", self.labels.iter().map(|label| label.name.as_str()).collect::<Vec<_>>().join(" "))?;
         };
         
         Ok(())
@@ -23,15 +29,11 @@ impl Display for Instruct {
 }
 
 impl Instruct {
-    pub fn new(pos: Pos, labels: Vec<String>, operation: Op) -> Self {
-        Self { pos, labels, operation }
-    }
-    
     pub fn pos(&self) -> Pos {
         self.pos
     }
     
-    pub fn labels(&self) -> &[String] {
+    pub fn labels(&self) -> &[Label] {
         &self.labels
     }
     
@@ -39,17 +41,40 @@ impl Instruct {
         &self.operation
     }
     
-    pub fn into_operation(self) -> Op {
-        self.operation
+    pub fn file(&self) -> Option<&PathBuf> {
+        self.file.as_deref()
+    }
+    
+    pub fn expansion(&self) -> &[Pos] {
+        &self.expansion
     }
+    
+    // position within the file itself, for instructs from macros that is where the outermost macro was called
+    pub fn site(&self) -> Pos {
+        self.expansion.first().copied().unwrap_or(self.pos())
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub name: String,
+    pub pos: Pos,
 }
 
 
 #[derive(Debug, Clone)]
 pub enum Op {
     InsertCpuInstruction(InstructionId, Vec<Argument>),
+    InsertPseudo(Pseudo, Vec<Argument>, Vec<(InstructionId, Vec<Argument>)>),  // with what it expands to
 
     SetVariable(String, u16),
+    Export(String),
+    Import(String),
+
+    Org(u16),
+    Align(u16),
+    Section(Section),
 
     InsertByte(u8),
     InsertWord(u16),
@@ -65,8 +90,13 @@ impl Op {
     pub fn size(&self) -> usize {
         match self {
             Self::InsertCpuInstruction(id, ..) => id.size(),
+            Self::InsertPseudo(_, _, instrs) => instrs.iter().map(|(id, _)| id.size()).sum(),
             
             Self::SetVariable(..) => 0,
+            Self::Export(..) | Self::Import(..) => 0,
+            
+            // padding of an alignment depends on where it is placed, that is up to the assembler
+            Self::Org(..) | Self::Align(..) | Self::Section(..) => 0,
             
             Self::InsertByte(..) => 1,
             Self::InsertWord(..) => 2,
@@ -83,14 +113,16 @@ impl Op {
 impl Display for Op {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::InsertCpuInstruction(id, args) =>
-                if args.is_empty() {
-                    write!(f, "{id}")
-                } else {
-                    write!(f, "{id} {}", args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().join(" "))
-                },
+            Self::InsertCpuInstruction(id, args) => write!(f, "{}", with_args(id, args)),
+            Self::InsertPseudo(pseudo, args, instrs) => write!(f, "{} => {}", with_args(pseudo, args), instrs.iter().map(|(id, args)| with_args(id, args)).collect::<Vec<_>>().join("; ")),
 
             Op::SetVariable(name, val) => write!(f, "!set %{name} #d{val}"),
+            Op::Export(name) => write!(f, "!export %{name}"),
+            Op::Import(name) => write!(f, "!import %{name}"),
+            
+            Op::Org(addr) => write!(f, "!org #x{addr:0>4x}"),
+            Op::Align(n) => write!(f, "!align #d{n}"),
+            Op::Section(section) => write!(f, "!section {section}"),
             
             Op::InsertByte(b) => write!(f, "!byte #d{b}"),
             Op::InsertWord(w) => write!(f, "!word #d{w}"),
@@ -104,6 +136,49 @@ impl Display for Op {
 }
 
 
+// every section has its own location counter, they are placed one after another in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Section {
+    Text,
+    Data,
+    Bss,  // only reserves space, nothing is stored
+}
+
+
+impl Section {
+    pub const ALL: [Self; 3] = [Self::Text, Self::Data, Self::Bss];
+}
+
+
+impl TryFrom<&str> for Section {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::ALL.into_iter().find(|section| section.to_string() == value).ok_or(())
+    }
+}
+
+
+impl Display for Section {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Data => write!(f, "data"),
+            Self::Bss => write!(f, "bss"),
+        }
+    }
+}
+
+
+fn with_args(name: impl Display, args: &[Argument]) -> String {
+    if args.is_empty() {
+        name.to_string()
+    } else {
+        format!("{name} {}", args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().join(" "))
+    }
+}
+
+
 #[derive(Debug, Clone)]
 pub enum Argument {
     Register(Register),
@@ -124,6 +199,7 @@ pub enum ValueArgument {
     Literal(u16),
     Reference(i16),
     Variable(String),
+    Expression(Box<Expr>),
 }
 
 
@@ -133,6 +209,59 @@ impl Display for ValueArgument {
             Self::Literal(n) => write!(f, "#d{n}"),
             Self::Reference(delta) => write!(f, "~{delta}"),
             Self::Variable(name) => write!(f, "%{name}"),
+            Self::Expression(expr) => write!(f, "{expr}"),
+        }
+    }
+}
+
+
+// constant expression, evaluated by the assembler once addresses of labels are known
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Value(ValueArgument),
+    Neg(Box<Expr>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+    Call(Function, Box<Expr>),
+}
+
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Value(val) => write!(f, "{val}"),
+            Self::Neg(expr) => write!(f, "-{expr}"),
+            Self::Binary(op, lhs, rhs) => write!(f, "({lhs} {op} {rhs})"),
+            Self::Call(func, arg) => write!(f, "{func}({arg})"),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
+pub enum Function {
+    Hi,  // upper byte
+    Lo,  // lower byte
+}
+
+
+impl TryFrom<&str> for Function {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "hi" => Ok(Self::Hi),
+            "lo" => Ok(Self::Lo),
+            _ => Err(()),
+        }
+    }
+}
+
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Hi => write!(f, "hi"),
+            Self::Lo => write!(f, "lo"),
         }
     }
 }
@@ -1,23 +1,194 @@
-use crate::parser::{Element, ElementValue};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use crate::lexer::Pos;
+use crate::parser::{Element, ElementValue, LiteralValue, Operator};
+use super::{InvalidElementInfo, ProcessingError};
+
+
+// what an argument has to look like, checked where the macro is called
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgKind {
+    Register,
+    Number,  // literal, label, reference or parenthesized expression
+    String,
+    Word,  // bare word, eg an instruction name
+}
+
+
+impl ArgKind {
+    fn fits(self, elem: &Element) -> bool {
+        match self {
+            Self::Register => matches!(elem.value, ElementValue::Register(_)),
+            Self::Number => matches!(elem.value,
+                ElementValue::Literal(LiteralValue::Number(_) | LiteralValue::Char(_))
+                | ElementValue::Variable(_)
+                | ElementValue::Reference(_)
+                | ElementValue::Operator(Operator::LeftParen)
+            ),
+            Self::String => matches!(elem.value, ElementValue::Literal(LiteralValue::String(_))),
+            Self::Word => matches!(elem.value, ElementValue::CpuInstruction(_)),
+        }
+    }
+}
+
+
+impl TryFrom<&str> for ArgKind {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "reg" => Ok(Self::Register),
+            "num" => Ok(Self::Number),
+            "str" => Ok(Self::String),
+            "word" => Ok(Self::Word),
+            _ => Err(()),
+        }
+    }
+}
+
+
+impl Display for ArgKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Register => write!(f, "a register"),
+            Self::Number => write!(f, "a number"),
+            Self::String => write!(f, "a string"),
+            Self::Word => write!(f, "a word"),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub(super) struct Param {
+    pub name: String,
+    pub kind: Option<ArgKind>,
+    pub default: Option<Element>,
+}
+
+
+impl Param {
+    // whether an argument starting with `elem` can be given to this parameter,
+    // a label or processor instruct always starts the next statement instead
+    pub fn accepts(&self, elem: &Element) -> bool {
+        !matches!(elem.value, ElementValue::Label(_) | ElementValue::ProcessorInstruction(_))
+            && self.kind.is_none_or(|kind| kind.fits(elem))
+    }
+}
 
 
 #[derive(Debug, Clone)]
 pub(super) struct Macro {
-    pub sub_count: usize,
+    pub params: Vec<Param>,
+    pub variadic: bool,  // last param takes any number of arguments
     pub source: Vec<Element>,
 }
 
 
+impl Macro {
+    // `!macro name #dN "source"`, with params named by their index
+    pub fn positional(count: usize, source: Vec<Element>) -> Self {
+        let params = (0..count).map(|i| Param { name: i.to_string(), kind: None, default: None }).collect();
+        Self { params, variadic: false, source }
+    }
+
+    // source with every substitute replaced by its arguments and `!for` blocks unrolled,
+    // `args` holds the arguments of each param (exactly one unless variadic),
+    // elements of the source itself get marked with the id of the expansion
+    pub fn expand<PE: Error + Clone>(&self, args: Vec<Vec<Vec<Element>>>, expansion: usize) -> Result<Vec<Element>, ProcessingError<PE>> {
+        let mut bindings = HashMap::new();
+        for (i, (param, args)) in self.params.iter().zip(args).enumerate() {
+            bindings.entry(i.to_string()).or_insert_with(|| args.clone());
+            bindings.insert(param.name.clone(), args);
+        };
+
+        let mut out = Vec::new();
+        substitute(&self.source, &mut bindings, expansion, &mut out)?;
+        Ok(out)
+    }
+}
+
+
+fn substitute<PE: Error + Clone>(elems: &[Element], bindings: &mut HashMap<String, Vec<Vec<Element>>>, expansion: usize, out: &mut Vec<Element>) -> Result<(), ProcessingError<PE>> {
+    let invalid = |elem: &Element, info| ProcessingError::InvalidElement { elem: elem.clone(), info };
+
+    let mut i = 0;
+    while let Some(elem) = elems.get(i) {
+        i += 1;
+        match &elem.value {
+            ElementValue::Substitute(name) => match bindings.get(name) {
+                Some(args) => out.extend(args.iter().flatten().cloned()),
+                None => { return Err(invalid(elem, InvalidElementInfo::MacroParam)); },
+            },
+            // `!for @item @list` ... `!endfor` repeats its body for every argument of list
+            ElementValue::ProcessorInstruction(instr) if instr == "for" => {
+                let (Some(Element { value: ElementValue::Substitute(item), .. }), Some(list @ Element { value: ElementValue::Substitute(list_name), .. })) = (elems.get(i), elems.get(i + 1)) else {
+                    return Err(invalid(elem, InvalidElementInfo::ProcessorInstructArg));
+                };
+                let Some(list) = bindings.get(list_name).cloned() else {
+                    return Err(invalid(list, InvalidElementInfo::MacroParam));
+                };
+
+                let start = i + 2;
+                let mut depth = 0;
+                let end = elems[start..].iter().position(|elem| match &elem.value {
+                    ElementValue::ProcessorInstruction(instr) if instr == "for" => { depth += 1; false },
+                    ElementValue::ProcessorInstruction(instr) if instr == "endfor" => {
+                        if depth == 0 {
+                            true
+                        } else {
+                            depth -= 1;
+                            false
+                        }
+                    },
+                    _ => false,
+                });
+                let Some(end) = end.map(|end| start + end) else {
+                    return Err(invalid(elem, InvalidElementInfo::UnclosedBlock));
+                };
+
+                let shadowed = bindings.remove(item);
+                for arg in list {
+                    bindings.insert(item.clone(), vec![arg]);
+                    substitute(&elems[start..end], bindings, expansion, out)?;
+                };
+                bindings.remove(item);
+                if let Some(shadowed) = shadowed {
+                    bindings.insert(item.clone(), shadowed);
+                };
+
+                i = end + 1;
+            },
+            ElementValue::ProcessorInstruction(instr) if instr == "endfor" => { return Err(invalid(elem, InvalidElementInfo::UnmatchedBlock)); },
+            _ => out.push(Element::new(elem.pos.with_expansion(expansion), elem.value.clone())),
+        };
+    };
+
+    Ok(())
+}
+
+
 pub(super) struct CurrentMacro {
-    subs: Vec<Element>,
     elems: Vec<Element>,
+    pub call_pos: Pos,
+    pub scope: String,  // local labels of each expansion are scoped to it alone
 }
 
 
 impl CurrentMacro {
-    pub fn new(mut elems: Vec<Element>, subs: Vec<Element>) -> Self {
+    pub fn new(mut elems: Vec<Element>, call_pos: Pos, scope: String) -> Self {
         elems.reverse();
-        Self { elems, subs }
+        Self { elems, call_pos, scope }
+    }
+
+    // puts an element read from this expansion back in front of it
+    pub fn unread(&mut self, elem: Element) {
+        self.elems.push(elem);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
     }
 }
 
@@ -26,9 +197,6 @@ impl Iterator for CurrentMacro {
     type Item = Element;
 
     fn next(&mut self) -> Option<Self::Item> {
-        Some(match self.elems.pop()? {
-            Element { value: ElementValue::Substitute(i), .. } => self.subs[i].clone(),
-            elem => elem,
-        })
+        self.elems.pop()
     }
 }
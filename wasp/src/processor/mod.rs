@@ -1,22 +1,32 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::error::Error;
 use std::ffi::CString;
 use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::str::Chars;
 use std::string::IntoChars;
 use normalize_path::NormalizePath;
 use resolve_path::PathResolveExt;
 use watto::InstructionId;
-use crate::parser::{Element, ElementValue, LiteralValue, Parser, ParsingError};
+use crate::parser::{Element, ElementValue, LiteralValue, Operator, Parser, ParsingError};
 use crate::lexer::{Lexer, LexingError, Pos};
-use r#macro::{CurrentMacro, Macro};
+use crate::diagnostic::{collect_all, SourceMap};
+use crate::fs::{FileSystem, RealFs};
+use r#macro::{CurrentMacro, Macro, Param};
+use cond::Cond;
 
+pub use cond::Defines;
+pub use r#macro::ArgKind;
 pub use err::{InvalidElementInfo, ProcessingError, ProcessorInitializationError};
-pub use instruct::{Argument, Instruct, Op, ValueArgument};
+pub use instruct::{Argument, Expr, Function, Instruct, Label, Op, Section, ValueArgument};
+pub use pseudo::{Pseudo, PseudoReference};
 
 mod instruct;
 mod r#macro;
+mod cond;
+mod pseudo;
 mod err;
 
 pub struct Processor<P, PE>
@@ -25,17 +35,27 @@ pub struct Processor<P, PE>
         PE: Error + Clone + 'static
 {
     parser: P,
-    err: Option<ProcessingError<PE>>,
+    recovering: bool,  // after an error elements are skipped up to the next statement
+    labels: Vec<Label>,  // for the next instruct, kept across errors
 
     paths_lib_root: Option<PathBuf>,  // todo allow specifying multiple lib directories
     paths_rel_root: Option<PathBuf>,
     allow_abs_paths: bool,
+    file: Option<Rc<PathBuf>>,
+    defines: Defines,
+    conds: Vec<Cond>,
 
     included_files: HashMap<PathBuf, HashMap<String, Macro>>,
 
     defined_macros: HashMap<String, Macro>,
     included_macros: HashMap<String, Macro>,
     cur_macro: Vec<CurrentMacro>,
+    sources: Rc<RefCell<SourceMap>>,  // shared with included files
+    fs: Rc<dyn FileSystem>,
+    scope: String,  // last global label, local labels (`.name`) are scoped to it
+    expansion: Vec<Pos>,  // of the element the current instruct starts with
+    peeked: Option<Option<Result<Element, PE>>>,
+    unread: Option<Result<Element, PE>>,  // put back in front of the parser
 
     cur_processor: Option<(Box<Processor<Parser<Lexer<IntoChars>, LexingError>, ParsingError<LexingError>>>, PathBuf, Pos)>,
 }
@@ -60,25 +80,342 @@ impl<P, PE> Processor<P, PE>
         proc_path!(paths_lib_root, FailedToProcessLibPath);
         proc_path!(paths_rel_root, FailedToProcessRelPath);
 
-        Ok(Self { parser, paths_lib_root, paths_rel_root, allow_abs_paths, included_files: HashMap::new(), err: None, included_macros: HashMap::new(), defined_macros: HashMap::new(), cur_processor: None, cur_macro: vec![] })
+        Ok(Self { parser, paths_lib_root, paths_rel_root, allow_abs_paths, file: None, defines: Defines::default(), conds: vec![], included_files: HashMap::new(), recovering: false, labels: vec![], included_macros: HashMap::new(), defined_macros: HashMap::new(), cur_processor: None, cur_macro: vec![], sources: Rc::default(), fs: Rc::new(RealFs), scope: String::new(), expansion: vec![], peeked: None, unread: None })
+    }
+    
+    // file the source comes from, reported with every instruct
+    pub fn with_file(mut self, file: Option<PathBuf>) -> Self {
+        self.file = file.map(Rc::new);
+        self
+    }
+    
+    // included files and macro expansions get registered in it, for errors to point into
+    pub fn with_sources(mut self, sources: Rc<RefCell<SourceMap>>) -> Self {
+        self.sources = sources;
+        self
+    }
+    
+    // where included files are read from
+    pub fn with_fs(mut self, fs: Rc<dyn FileSystem>) -> Self {
+        self.fs = fs;
+        self
+    }
+    
+    pub fn with_defines(mut self, defines: Defines) -> Self {
+        self.defines = defines;
+        self
+    }
+    
+    // whether elements are currently assembled (not within a false branch of a conditional block)
+    fn is_active(&self) -> bool {
+        self.conds.last().is_none_or(|cond| cond.active)
+    }
+    
+    fn instruct(&mut self, pos: Pos, operation: Op) -> Option<Result<Instruct, ProcessingError<PE>>> {
+        Some(Ok(Instruct { pos, labels: std::mem::take(&mut self.labels), operation, file: self.file.clone(), expansion: self.expansion.clone() }))
     }
     
     fn err(&mut self, err: ProcessingError<PE>) -> Option<Result<Instruct, ProcessingError<PE>>> {
-        self.err = Some(err.clone());
+        self.recovering = true;
         Some(Err(err))
     }
     
+    // next element as written, local labels are left unscoped
+    fn next_raw_el(&mut self) -> Option<Result<Element, PE>> {
+        while self.cur_macro.last().is_some_and(CurrentMacro::is_empty) {
+            self.cur_macro.pop();
+        };
+
+        match self.cur_macro.last_mut() {
+            Some(cur_macro) => cur_macro.next().map(Ok),
+            // todo handle not in macro
+            None => self.unread.take().or_else(|| self.parser.next()),
+        }
+    }
+    
     fn next_el(&mut self) -> Option<Result<Element, PE>> {
-        while let Some(cur_macro) = self.cur_macro.last_mut() {
-            if let Some(el) = cur_macro.next() {
-                return Some(Ok(el))
+        if let Some(el) = self.peeked.take() {
+            return el;
+        };
+        
+        let el = self.next_raw_el()?;
+        if let Some(cur_macro) = self.cur_macro.last() {
+            return Some(el.map(|el| scoped(el, &cur_macro.scope)));
+        };
+
+        match el {
+            Ok(Element { value: ElementValue::Label(name), pos }) if !name.starts_with('.') && self.is_active() => {
+                self.scope = name.clone();
+                Some(Ok(Element::new(pos, ElementValue::Label(name))))
+            },
+            el => Some(el.map(|el| scoped(el, &self.scope))),
+        }
+    }
+    
+    fn peek_el(&mut self) -> Option<&Result<Element, PE>> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.next_el());
+        };
+        
+        self.peeked.as_ref().unwrap().as_ref()
+    }
+    
+    fn next_operand(&mut self) -> Result<Expr, ProcessingError<PE>> {
+        match self.next_el() {
+            Some(Ok(elem)) => self.operand(elem),
+            Some(Err(err)) => Err(ProcessingError::ParsingFailure(err)),
+            None => Err(ProcessingError::EarlyEoE),
+        }
+    }
+    
+    fn operand(&mut self, elem: Element) -> Result<Expr, ProcessingError<PE>> {
+        let invalid = |elem| ProcessingError::InvalidElement { elem, info: InvalidElementInfo::CpuInstructionArg { expected: watto::Argument::Number } };
+        
+        Ok(match elem {
+            Element { value: ElementValue::Literal(LiteralValue::Number(n)), .. } => Expr::Value(ValueArgument::Literal(n)),
+            Element { value: ElementValue::Literal(LiteralValue::Char(c)), pos } => match c.as_ascii() {
+                Some(cc) => Expr::Value(ValueArgument::Literal(cc.to_u8() as u16)),
+                None => { return Err(ProcessingError::InvalidElement { elem: Element::new(pos, ElementValue::Literal(LiteralValue::Char(c))), info: InvalidElementInfo::NonAsciiCharAsArg }); },
+            },
+            Element { value: ElementValue::Reference(delta), .. } => Expr::Value(ValueArgument::Reference(delta)),
+            Element { value: ElementValue::Variable(name), .. } => Expr::Value(ValueArgument::Variable(name)),
+            // unary minus binds tighter than any binary operator
+            Element { value: ElementValue::Operator(Operator::Sub), .. } => Expr::Neg(Box::new(self.next_operand()?)),
+            Element { value: ElementValue::Operator(Operator::LeftParen), .. } => self.closed_expr()?,
+            Element { value: ElementValue::CpuInstruction(name), pos } => match Function::try_from(name.as_str()) {
+                Ok(func) => match self.next_el() {
+                    Some(Ok(Element { value: ElementValue::Operator(Operator::LeftParen), .. })) => Expr::Call(func, Box::new(self.closed_expr()?)),
+                    Some(Ok(elem)) => { return Err(invalid(elem)); },
+                    Some(Err(err)) => { return Err(ProcessingError::ParsingFailure(err)); },
+                    None => { return Err(ProcessingError::EarlyEoE); },
+                },
+                Err(()) => { return Err(invalid(Element::new(pos, ElementValue::CpuInstruction(name)))); },
+            },
+            elem => { return Err(invalid(elem)); },
+        })
+    }
+    
+    // rest of an expression after its opening parenthesis
+    fn closed_expr(&mut self) -> Result<Expr, ProcessingError<PE>> {
+        let first = self.next_operand()?;
+        let expr = self.expr(first, 0)?;
+        match self.next_el() {
+            Some(Ok(Element { value: ElementValue::Operator(Operator::RightParen), .. })) => Ok(expr),
+            Some(Ok(elem)) => Err(ProcessingError::InvalidElement { elem, info: InvalidElementInfo::UnclosedParenthesis }),
+            Some(Err(err)) => Err(ProcessingError::ParsingFailure(err)),
+            None => Err(ProcessingError::EarlyEoE),
+        }
+    }
+    
+    // precedence climbing, continues for as long as a binary operator follows
+    fn expr(&mut self, mut lhs: Expr, min_prec: u8) -> Result<Expr, ProcessingError<PE>> {
+        while let Some(Ok(Element { value: ElementValue::Operator(op), .. })) = self.peek_el()
+            && let Some(prec) = op.precedence()
+            && prec >= min_prec {
+            let op = *op;
+            self.next_el();
+            
+            let mut rhs = self.next_operand()?;
+            while let Some(Ok(Element { value: ElementValue::Operator(next), .. })) = self.peek_el()
+                && next.precedence().is_some_and(|next| next > prec) {
+                let next = next.precedence().unwrap();
+                rhs = self.expr(rhs, next)?;
+            };
+            
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        };
+        
+        Ok(lhs)
+    }
+    
+    // arguments of a cpu (or pseudo) instruction at `pos`, all of them on its line
+    fn cpu_args(&mut self, pos: Pos, name: &str, expected: Vec<watto::Argument>) -> Result<Vec<Argument>, ProcessingError<PE>> {
+        let depth = self.cur_macro.len();
+        let mut args = Vec::new();
+        for expected in expected.into_iter() {
+            if !self.on_line(pos, depth) && !matches!(self.peek_el(), Some(Err(_))) {
+                return Err(ProcessingError::InvalidElement { elem: Element::new(pos, ElementValue::CpuInstruction(name.to_string())), info: InvalidElementInfo::MissingCpuInstructionArg { expected } });
+            };
+            
+            match self.next_el() {
+                Some(Ok(elem)) => {
+                    match expected {
+                        watto::Argument::Register => {
+                            match elem {
+                                Element { value: ElementValue::Register(reg), .. } => { args.push(Argument::Register(reg)); },
+                                elem => { return Err(ProcessingError::InvalidElement { elem, info: InvalidElementInfo::CpuInstructionArg { expected } }); },
+                            };
+                        },
+                        watto::Argument::Number => {
+                            let expr = self.operand(elem).and_then(|first| self.expr(first, 0))?;
+                            args.push(Argument::Value(match expr {
+                                Expr::Value(val) => val,
+                                expr => ValueArgument::Expression(Box::new(expr)),
+                            }));
+                        }
+                    };
+                },
+                Some(Err(err)) => { return Err(ProcessingError::ParsingFailure(err)); },
+                None => { return Err(ProcessingError::EarlyEoE); }
+            }
+        };
+        
+        Ok(args)
+    }
+    
+    // skips to where the next statement starts: a label, processor instruct or cpu instruction
+    // (unknown ones too, so that they get reported), parsing errors on the way are still reported
+    fn resync(&mut self) -> Option<PE> {
+        while let Some(el) = self.peek_el() {
+            match el {
+                Ok(Element { value: ElementValue::Label(_) | ElementValue::ProcessorInstruction(_) | ElementValue::CpuInstruction(_), .. }) => break,
+                Ok(_) => { self.next_el(); },
+                Err(_) => { return self.next_el()?.err(); },
+            };
+        };
+        
+        self.recovering = false;
+        None
+    }
+    
+    // a peeked element goes back to where it came from (so that it comes after a macro expanded before it)
+    fn unpeek(&mut self) {
+        if let Some(Some(el)) = self.peeked.take() {
+            match (self.cur_macro.last_mut(), el) {
+                (Some(cur_macro), Ok(el)) => cur_macro.unread(el),
+                (_, el) => { self.unread = Some(el); },
+            };
+        };
+    }
+    
+    // single element or a parenthesized group of them
+    fn next_arg(&mut self) -> Result<Vec<Element>, ProcessingError<PE>> {
+        let mut arg = Vec::new();
+        let mut depth = 0;
+        loop {
+            let elem = match self.next_el() {
+                Some(Ok(elem)) => elem,
+                Some(Err(err)) => { return Err(ProcessingError::ParsingFailure(err)); },
+                None => { return Err(ProcessingError::EarlyEoE); },
+            };
+            
+            match elem.value {
+                ElementValue::Operator(Operator::LeftParen) => { depth += 1; },
+                ElementValue::Operator(Operator::RightParen) => { depth -= 1; },
+                _ => {},
+            };
+            arg.push(elem);
+            
+            if depth <= 0 {
+                return Ok(arg);
+            };
+        };
+    }
+    
+    // whether the next element is on the same line as `call` (substituted arguments count as being where they were substituted),
+    // nothing after the end of the macro expansion `call` is in is
+    fn on_line(&mut self, call: Pos, depth: usize) -> bool {
+        if self.peeked.is_none() && depth != 0 && self.cur_macro.get(depth - 1).is_none_or(CurrentMacro::is_empty) {
+            return false;
+        };
+        
+        match self.peek_el() {
+            Some(Ok(Element { pos, .. })) => (pos.file() == call.file() && pos.expansion() == call.expansion() && pos.line() == call.line())
+                || (depth != 0 && pos.expansion() != call.expansion()),
+            _ => false,
+        }
+    }
+    
+    // arguments of each param of a macro being called at `call`,
+    // optional params only take them from the rest of the line
+    fn macro_args(&mut self, r#macro: &Macro, call: Pos) -> Result<Vec<Vec<Vec<Element>>>, ProcessingError<PE>> {
+        let depth = self.cur_macro.len();
+        let mut args = Vec::new();
+        for (i, param) in r#macro.params.iter().enumerate() {
+            let variadic = r#macro.variadic && i + 1 == r#macro.params.len();
+            let mut param_args = Vec::new();
+            
+            // optional params only take arguments that fit them
+            if variadic || param.default.is_some() {
+                while self.on_line(call, depth)
+                    && let Some(Ok(elem)) = self.peek_el()
+                    && param.accepts(elem) {
+                    param_args.push(self.next_arg()?);
+                    if !variadic {
+                        break;
+                    };
+                };
+                
+                if let Some(default) = &param.default && param_args.is_empty() && !variadic {
+                    param_args.push(vec![default.clone()]);
+                };
             } else {
-                self.cur_macro.pop();
+                let arg = self.next_arg()?;
+                if let Some(kind) = param.kind && !param.accepts(&arg[0]) {
+                    return Err(ProcessingError::InvalidElement { elem: arg[0].clone(), info: InvalidElementInfo::MacroArgKind { expected: kind } });
+                };
+                param_args.push(arg);
+            };
+            
+            args.push(param_args);
+        };
+        
+        Ok(args)
+    }
+    
+    // `!macro name @param [kind] [default] ... [...]` with the params on the same line as the name,
+    // followed by the source up to `!endmacro`
+    fn macro_block(&mut self, macro_elem: Element, name_pos: Pos, first: Option<Result<Element, PE>>) -> Result<Macro, ProcessingError<PE>> {
+        let mut params: Vec<Param> = Vec::new();
+        let mut variadic = false;
+        let mut source = Vec::new();
+        
+        let mut next = first;
+        loop {
+            let elem = match next.take().or_else(|| self.next_raw_el()) {
+                Some(Ok(elem)) => elem,
+                Some(Err(err)) => { return Err(ProcessingError::ParsingFailure(err)); },
+                None => { return Err(ProcessingError::InvalidElement { elem: macro_elem, info: InvalidElementInfo::UnclosedBlock }); },
+            };
+            
+            if !source.is_empty() || elem.pos.line() != name_pos.line() {
+                match elem.value {
+                    ElementValue::ProcessorInstruction(instr) if instr == "endmacro" => { break; },
+                    _ => source.push(elem),
+                };
+                continue;
+            };
+            
+            let invalid = |elem| ProcessingError::InvalidElement { elem, info: InvalidElementInfo::ProcessorInstructArg };
+            match (elem.value, params.last_mut()) {
+                (ElementValue::Substitute(name), _) if !variadic => params.push(Param { name, kind: None, default: None }),
+                (ElementValue::CpuInstruction(word), Some(_)) if word == "..." && !variadic => { variadic = true; },
+                (ElementValue::CpuInstruction(word), Some(param)) if param.kind.is_none() && param.default.is_none() && !variadic => match ArgKind::try_from(word.as_str()) {
+                    Ok(kind) => { param.kind = Some(kind); },
+                    Err(()) => { return Err(invalid(Element::new(elem.pos, ElementValue::CpuInstruction(word)))); },
+                },
+                (value, Some(param)) if param.default.is_none() && !variadic => {
+                    let default = Element::new(elem.pos, value);
+                    if !param.accepts(&default) {
+                        return Err(ProcessingError::InvalidElement { elem: default, info: InvalidElementInfo::MacroArgKind { expected: param.kind.unwrap() } });
+                    };
+                    param.default = Some(default);
+                },
+                (value, _) => { return Err(invalid(Element::new(elem.pos, value))); },
             };
         };
+        
+        Ok(Macro { params, variadic, source })
+    }
+}
+
 
-        // todo handle not in macro
-        self.parser.next()
+// local labels and variables (`.name`) get prefixed with their scope
+fn scoped(elem: Element, scope: &str) -> Element {
+    match elem {
+        Element { value: ElementValue::Label(name), pos } if name.starts_with('.') => Element::new(pos, ElementValue::Label(format!("{scope}{name}"))),
+        Element { value: ElementValue::Variable(name), pos } if name.starts_with('.') => Element::new(pos, ElementValue::Variable(format!("{scope}{name}"))),
+        elem => elem,
     }
 }
 
@@ -126,7 +463,7 @@ macro_rules! nextpath {
 macro_rules! nextfile {
     ($s:expr, bin, rel, $path:ident, $data:ident, $code:expr) => {
         nextpath!{ $s, rel, $path, epos, {
-            match std::fs::read(&$path) {
+            match $s.fs.read(&$path) {
                 Ok($data) => $code,
                 Err(err) => { return $s.err(ProcessingError::InvalidElement { elem: Element::new(epos, ElementValue::Literal(LiteralValue::String($path.to_string_lossy().to_string()))), info: InvalidElementInfo::FailedToReadFile { reason: err.to_string() } }); },  // fixme provide correct pos
             };
@@ -134,7 +471,7 @@ macro_rules! nextfile {
     };
     ($s:expr, bin, lib, $path:ident, $data:ident, $code:expr) => {
         nextpath!{ $s, lib, $path, epos, {
-            match std::fs::read(&$path) {
+            match $s.fs.read(&$path) {
                 Ok($data) => $code,
                 Err(err) => { return $s.err(ProcessingError::InvalidElement { elem: Element::new(epos, ElementValue::Literal(LiteralValue::String($path.to_string_lossy().to_string()))), info: InvalidElementInfo::FailedToReadFile { reason: err.to_string() } }); },  // fixme provide correct pos
             };
@@ -142,7 +479,7 @@ macro_rules! nextfile {
     };
     ($s:expr, str, rel, $path:ident, $data:ident, $code:expr) => {
         nextpath!{ $s, rel, $path, epos, {
-            match std::fs::read_to_string(&$path) {
+            match $s.fs.read_to_string(&$path) {
                 Ok($data) => $code,
                 Err(err) => { return $s.err(ProcessingError::InvalidElement { elem: Element::new(epos, ElementValue::Literal(LiteralValue::String($path.to_string_lossy().to_string()))), info: InvalidElementInfo::FailedToReadFile { reason: err.to_string() } }); },  // fixme provide correct pos
             };
@@ -150,7 +487,7 @@ macro_rules! nextfile {
     };
     ($s:expr, str, lib, $path:ident, $data:ident, $code:expr) => {
         nextpath!{ $s, lib, $path, epos, {
-            match std::fs::read_to_string(&$path) {
+            match $s.fs.read_to_string(&$path) {
                 Ok($data) => $code,
                 Err(err) => { return $s.err(ProcessingError::InvalidElement { elem: Element::new(epos, ElementValue::Literal(LiteralValue::String($path.to_string_lossy().to_string()))), info: InvalidElementInfo::FailedToReadFile { reason: err.to_string() } }); },  // fixme provide correct pos
             };
@@ -187,15 +524,11 @@ impl<P, PE> Iterator for Processor<P, PE>
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            if let Some(err) = self.err.clone() {
-                return Some(Err(err));
-            };
-
-            let mut err = None;
             if let Some((proc, path, pos)) = &mut self.cur_processor {
                 match proc.next() {
                     Some(Ok(instr)) => { return Some(Ok(instr)); },
-                    Some(Err(err_)) => { err = Some(ProcessingError::InvalidElement { elem: Element::new(*pos, ElementValue::Literal(LiteralValue::String(path.to_string_lossy().to_string()))), info: InvalidElementInfo::IncludedFileProcessingFailure(Box::new(err_)) }); },
+                    // the included processor recovers on its own
+                    Some(Err(err)) => { return Some(Err(ProcessingError::InvalidElement { elem: Element::new(*pos, ElementValue::Literal(LiteralValue::String(path.to_string_lossy().to_string()))), info: InvalidElementInfo::IncludedFileProcessingFailure(Box::new(err)) })); },
                     None => {
                         self.included_files.extend(std::mem::take(&mut proc.included_files).into_iter());
                         self.included_macros.extend(proc.defined_macros.iter().map(|(k, v)| (k.clone(), v.clone())));
@@ -204,55 +537,50 @@ impl<P, PE> Iterator for Processor<P, PE>
                     },
                 };
             };
-            if let Some(err) = err {
-                return self.err(err);
-            };
-
-            let mut labels = Vec::new();
 
             loop {
-                match self.next_el()? {
+                if self.recovering && let Some(err) = self.resync() {
+                    return Some(Err(ProcessingError::ParsingFailure(err)));
+                };
+                
+                let Some(elem) = self.next_el() else {
+                    if let Some(cond) = self.conds.pop() {
+                        return self.err(ProcessingError::InvalidElement { elem: cond.opened_by, info: InvalidElementInfo::UnclosedConditional });
+                    };
+                    return None;
+                };
+                self.expansion = self.cur_macro.iter().map(|m| m.call_pos).collect();
+                
+                // within a false branch only conditionals themselves are looked at (to keep track of nesting)
+                if !self.is_active()
+                    && !matches!(&elem, Ok(Element { value: ElementValue::ProcessorInstruction(name), .. }) if matches!(name.as_str(), "iffeat" | "ifenv" | "else" | "endif")) {
+                    match elem {
+                        Ok(_) => { continue; },
+                        Err(err) => { return self.err(ProcessingError::ParsingFailure(err)); },
+                    };
+                };
+                
+                match elem {
                     Ok(elem) =>
                         match elem {
                             Element { pos, value: ElementValue::CpuInstruction(name) } => {
-                                match InstructionId::try_from(name.as_str()) {
-                                    Ok(id) => {
-                                        let mut args = Vec::new();
-                                        for expected in id.arguments().into_iter() {
-                                            match self.next_el() {
-                                                Some(Ok(elem)) => {
-                                                    match expected {
-                                                        watto::Argument::Register => {
-                                                            match elem {
-                                                                Element { value: ElementValue::Register(reg), .. } => { args.push(Argument::Register(reg)); },
-                                                                elem => { return self.err(ProcessingError::InvalidElement { elem, info: InvalidElementInfo::CpuInstructionArg { expected } }); },
-                                                            };
-                                                        },
-                                                        watto::Argument::Number => {
-                                                            match elem {
-                                                                Element { value: ElementValue::Literal(lit), pos } =>
-                                                                    match lit {
-                                                                        LiteralValue::Char(c) => {
-                                                                            match c.as_ascii() {
-                                                                                Some(cc) => args.push(Argument::Value(ValueArgument::Literal(cc.to_u8() as u16))),
-                                                                                None => { return self.err(ProcessingError::InvalidElement { elem: Element::new(pos, ElementValue::Literal(LiteralValue::Char(c))), info: InvalidElementInfo::NonAsciiCharAsArg }); },
-                                                                            }
-                                                                        },
-                                                                        LiteralValue::Number(n) => args.push(Argument::Value(ValueArgument::Literal(n))),
-                                                                        _ => { return self.err(ProcessingError::InvalidElement { elem: Element::new(pos, ElementValue::Literal(lit)), info: InvalidElementInfo::CpuInstructionArg { expected } }); }
-                                                                    },
-                                                                Element { value: ElementValue::Reference(delta), .. } => { args.push(Argument::Value(ValueArgument::Reference(delta))); },
-                                                                Element { value: ElementValue::Variable(name), .. } => { args.push(Argument::Value(ValueArgument::Variable(name))); },
-                                                                elem => { return self.err(ProcessingError::InvalidElement { elem, info: InvalidElementInfo::CpuInstructionArg { expected } }); },
-                                                            }
-                                                        }
-                                                    };
-                                                },
-                                                Some(Err(err)) => { return self.err(ProcessingError::ParsingFailure(err)); },
-                                                None => { return self.err(ProcessingError::EarlyEoE); }
-                                            }
+                                if let Ok(id) = InstructionId::try_from(name.as_str()) {
+                                    return match self.cpu_args(pos, &name, id.arguments()) {
+                                        Ok(args) => self.instruct(pos, Op::InsertCpuInstruction(id, args)),
+                                        Err(err) => self.err(err),
+                                    };
+                                };
+                                
+                                match Pseudo::try_from(name.as_str()) {
+                                    Ok(pseudo) => {
+                                        let args = match self.cpu_args(pos, &name, pseudo.arguments()) {
+                                            Ok(args) => args,
+                                            Err(err) => { return self.err(err); },
+                                        };
+                                        match pseudo.expand(&args) {
+                                            Some(instrs) => { return self.instruct(pos, Op::InsertPseudo(pseudo, args, instrs)); },
+                                            None => { return self.err(ProcessingError::InvalidElement { elem: Element::new(pos, ElementValue::CpuInstruction(name)), info: InvalidElementInfo::PseudoArg }); },
                                         };
-                                        return Some(Ok(Instruct { pos, labels, operation: Op::InsertCpuInstruction(id, args) }));
                                     },
                                     Err(()) => { return self.err(ProcessingError::InvalidElement { elem: Element::new(pos, ElementValue::CpuInstruction(name)), info: InvalidElementInfo::CpuInstructionName }); }
                                 };
@@ -262,7 +590,7 @@ impl<P, PE> Iterator for Processor<P, PE>
                                     "byte" => nextcel!{ self,
                                     Element { value: ElementValue::Literal(LiteralValue::Number(n)), pos: epos } => {
                                         match n.try_into() {
-                                            Ok(b) => { return Some(Ok(Instruct { pos, labels, operation: Op::InsertByte(b) })); },
+                                            Ok(b) => { return self.instruct(pos, Op::InsertByte(b)); },
                                             Err(_) => { return self.err(ProcessingError::InvalidElement { elem: Element::new(epos, ElementValue::Literal(LiteralValue::Number(n))), info: InvalidElementInfo::ProcessorInstructArg }) },
                                         };
                                     }
@@ -279,20 +607,20 @@ impl<P, PE> Iterator for Processor<P, PE>
 
                                         let count = nextcel!{ self, Element { value: ElementValue::Literal(LiteralValue::Number(n)), .. } => n };
 
-                                        return Some(Ok(Instruct { pos, labels, operation: Op::InsertMultipleBytes(b, count) }));
+                                        return self.instruct(pos, Op::InsertMultipleBytes(b, count));
                                     }
                                     "word" => nextcel!{ self,
                                     Element { value: ElementValue::Literal(LiteralValue::Number(n)), .. } => {
-                                        return Some(Ok(Instruct { pos, labels, operation: Op::InsertWord(n) }));
+                                        return self.instruct(pos, Op::InsertWord(n));
                                     }
                                 },
                                     "file" => nextfile!{ self, bin, rel, path, bytes, {
-                                    return Some(Ok(Instruct { pos, labels, operation: Op::InsertBytes(bytes) }));
+                                    return self.instruct(pos, Op::InsertBytes(bytes));
                                 }},
                                     "cstr" => nextcel!{ self,
                                     Element { value: ElementValue::Literal(LiteralValue::String(s)), pos: epos } => {
                                         match CString::new(s) {
-                                            Ok(cstr) => { return Some(Ok(Instruct { pos, labels, operation: Op::InsertCString(cstr) })); },
+                                            Ok(cstr) => { return self.instruct(pos, Op::InsertCString(cstr)); },
                                             Err(err) => { return self.err(ProcessingError::InvalidElement { elem: Element::new(epos, ElementValue::Literal(LiteralValue::String(unsafe { String::from_utf8_unchecked(err.into_vec()) }))), info: InvalidElementInfo::ProcessorInstructArg }) },
                                         };
                                     }
@@ -301,7 +629,36 @@ impl<P, PE> Iterator for Processor<P, PE>
                                         let name = nextcel!{ self, Element { value: ElementValue::Variable(name), .. } => name };
                                         let val = nextcel!{ self, Element { value: ElementValue::Literal(LiteralValue::Number(n)), .. } => n };
 
-                                        return Some(Ok(Instruct { pos, labels, operation: Op::SetVariable(name, val) }));
+                                        return self.instruct(pos, Op::SetVariable(name, val));
+                                    },
+                                    "org" => nextcel!{ self,
+                                    Element { value: ElementValue::Literal(LiteralValue::Number(addr)), .. } => {
+                                        return self.instruct(pos, Op::Org(addr));
+                                    }
+                                },
+                                    "align" => nextcel!{ self,
+                                    Element { value: ElementValue::Literal(LiteralValue::Number(n)), pos: epos } => {
+                                        if n == 0 {
+                                            return self.err(ProcessingError::InvalidElement { elem: Element::new(epos, ElementValue::Literal(LiteralValue::Number(n))), info: InvalidElementInfo::ProcessorInstructArg });
+                                        };
+                                        return self.instruct(pos, Op::Align(n));
+                                    }
+                                },
+                                    "section" => nextcel!{ self,
+                                    Element { value: ElementValue::CpuInstruction(section), pos: epos } => {
+                                        match Section::try_from(section.as_str()) {
+                                            Ok(section) => { return self.instruct(pos, Op::Section(section)); },
+                                            Err(()) => { return self.err(ProcessingError::InvalidElement { elem: Element::new(epos, ElementValue::CpuInstruction(section)), info: InvalidElementInfo::ProcessorInstructArg }); },
+                                        };
+                                    }
+                                },
+                                    "export" => {
+                                        let name = nextcel!{ self, Element { value: ElementValue::Variable(name), .. } => name };
+                                        return self.instruct(pos, Op::Export(name));
+                                    },
+                                    "import" => {
+                                        let name = nextcel!{ self, Element { value: ElementValue::Variable(name), .. } => name };
+                                        return self.instruct(pos, Op::Import(name));
                                     },
                                     "include" => nextfile!{ self, str, rel, path, code, {
                                     if let Some(macros) = self.included_files.get(&path) {
@@ -309,7 +666,8 @@ impl<P, PE> Iterator for Processor<P, PE>
                                         continue;
                                     };
 
-                                    let mut processor = Processor::new(Parser::new(Lexer::new(code.into_chars())), self.paths_lib_root.clone(), Some(path.parent().unwrap().to_path_buf()), self.allow_abs_paths).unwrap();
+                                    let file = self.sources.borrow_mut().add_path(Some(&path), code.clone());
+                                    let mut processor = Processor::new(Parser::new(Lexer::new(code.into_chars()).with_file(file)), self.paths_lib_root.clone(), Some(path.parent().unwrap().to_path_buf()), self.allow_abs_paths).unwrap().with_file(Some(path.clone())).with_defines(self.defines.clone()).with_sources(self.sources.clone()).with_fs(self.fs.clone());
                                     processor.included_files = std::mem::take(&mut self.included_files);
                                     self.cur_processor = Some((Box::new(processor), path, pos));
                                     break;
@@ -320,53 +678,90 @@ impl<P, PE> Iterator for Processor<P, PE>
                                         continue;
                                     };
 
-                                    let mut processor = Processor::new(Parser::new(Lexer::new(code.into_chars())), self.paths_lib_root.clone(), Some(path.parent().unwrap().to_path_buf()), true).unwrap();
+                                    let file = self.sources.borrow_mut().add_path(Some(&path), code.clone());
+                                    let mut processor = Processor::new(Parser::new(Lexer::new(code.into_chars()).with_file(file)), self.paths_lib_root.clone(), Some(path.parent().unwrap().to_path_buf()), true).unwrap().with_file(Some(path.clone())).with_defines(self.defines.clone()).with_sources(self.sources.clone()).with_fs(self.fs.clone());
                                     processor.included_files = std::mem::take(&mut self.included_files);
                                     self.cur_processor = Some((Box::new(processor), path, pos));
                                     break;
                                 }},
                                     "macro" => {
-                                        let name = nextcel!{ self, Element { value: ElementValue::CpuInstruction(name), .. } => name };
-                                        let arg_count = nextcel!{ self, Element { value: ElementValue::Literal(LiteralValue::Number(n)), .. } => n as usize };
-                                        let source = nextcel!{ self,
-                                        Element { value: ElementValue::Literal(LiteralValue::String(source)), pos } => {
-                                            match Parser::parse(&source) {
-                                                Ok(elems) => elems,
-                                                Err(err) => { return self.err(ProcessingError::InvalidElement { elem: Element::new(pos, ElementValue::Literal(LiteralValue::String(source))), info: InvalidElementInfo::IncludedCodeParsingFailure(err) }); },
-                                            }
-                                        }
-                                    };
+                                        let (macro_name, name_pos) = nextcel!{ self, Element { value: ElementValue::CpuInstruction(name), pos } => (name, pos) };
+                                        let r#macro = match self.next_raw_el() {
+                                            Some(Ok(Element { value: ElementValue::Literal(LiteralValue::Number(arg_count)), .. })) => {
+                                                let source = nextcel!{ self,
+                                                Element { value: ElementValue::Literal(LiteralValue::String(source)), pos } => {
+                                                    let file = self.sources.borrow_mut().add_file(format!("macro `{macro_name}`"), source.clone());
+                                                    match Parser::new(Lexer::new(source.chars()).with_file(file)).try_collect() {
+                                                        Ok(elems) => elems,
+                                                        Err(err) => { return self.err(ProcessingError::InvalidElement { elem: Element::new(pos, ElementValue::Literal(LiteralValue::String(source))), info: InvalidElementInfo::IncludedCodeParsingFailure(Box::new(err)) }); },
+                                                    }
+                                                }
+                                            };
+                                                Macro::positional(arg_count as usize, source)
+                                            },
+                                            first => match self.macro_block(Element::new(pos, ElementValue::ProcessorInstruction(name)), name_pos, first) {
+                                                Ok(r#macro) => r#macro,
+                                                Err(err) => { return self.err(err); },
+                                            },
+                                        };
 
-                                        self.defined_macros.insert(name, Macro { sub_count: arg_count, source });
+                                        self.defined_macros.insert(macro_name, r#macro);
                                     },
+                                    "endmacro" => { return self.err(ProcessingError::InvalidElement { elem: Element::new(pos, ElementValue::ProcessorInstruction(name)), info: InvalidElementInfo::UnmatchedBlock }); },
                                     "m" => {
-                                        let r#macro = nextcel!{ self,
+                                        let (name, r#macro) = nextcel!{ self,
                                         Element { value: ElementValue::CpuInstruction(name), pos } => {
                                             match self.defined_macros.get(&name).or_else(|| self.included_macros.get(&name)) {
-                                                Some(m) => m,
+                                                Some(m) => (name, m.clone()),
                                                 None => { return self.err(ProcessingError::InvalidElement { elem: Element::new(pos, ElementValue::CpuInstruction(name)), info: InvalidElementInfo::MacroName }); },
                                             }
                                         }
                                     };
 
-                                        let source = r#macro.source.clone();
-                                        let mut subs = Vec::new();
-                                        for _ in 0..r#macro.sub_count {
-                                            subs.push(nextcel! { self });
+                                        let args = match self.macro_args(&r#macro, pos) {
+                                            Ok(args) => args,
+                                            Err(err) => { return self.err(err); },
+                                        };
+                                        let expansion = self.sources.borrow_mut().add_expansion(name.clone(), pos);
+                                        let source = match r#macro.expand(args, expansion) {
+                                            Ok(source) => source,
+                                            Err(err) => { return self.err(err); },
                                         };
 
-                                        self.cur_macro.push(CurrentMacro::new(source, subs));
+                                        self.unpeek();
+                                        self.cur_macro.push(CurrentMacro::new(source, pos, format!("{name}.{expansion}")));
+                                    },
+                                    "iffeat" => {
+                                        let parent_active = self.is_active();
+                                        let feature = nextcel!{ self, Element { value: ElementValue::CpuInstruction(feature), .. } => feature };
+
+                                        let active = parent_active && self.defines.features.contains(&feature);
+                                        self.conds.push(Cond { active, parent_active, has_else: false, opened_by: Element::new(pos, ElementValue::ProcessorInstruction(name)) });
                                     },
                                     "ifenv" => {
-                                        todo!()
+                                        let parent_active = self.is_active();
+                                        let var = nextcel!{ self, Element { value: ElementValue::CpuInstruction(var), .. } => var };
+                                        let val = nextcel!{ self, Element { value: ElementValue::Literal(LiteralValue::String(val)), .. } => val };
+
+                                        let active = parent_active && self.defines.env.get(&var) == Some(&val);
+                                        self.conds.push(Cond { active, parent_active, has_else: false, opened_by: Element::new(pos, ElementValue::ProcessorInstruction(name)) });
                                     },
-                                    "iffeat" => {
-                                        todo!()
+                                    "else" => match self.conds.last_mut() {
+                                        Some(cond) if !cond.has_else => {
+                                            cond.has_else = true;
+                                            cond.active = cond.parent_active && !cond.active;
+                                        },
+                                        _ => { return self.err(ProcessingError::InvalidElement { elem: Element::new(pos, ElementValue::ProcessorInstruction(name)), info: InvalidElementInfo::UnmatchedConditional }); },
+                                    },
+                                    "endif" => {
+                                        if self.conds.pop().is_none() {
+                                            return self.err(ProcessingError::InvalidElement { elem: Element::new(pos, ElementValue::ProcessorInstruction(name)), info: InvalidElementInfo::UnmatchedConditional });
+                                        };
                                     },
-                                    "void" => { return Some(Ok(Instruct { pos, labels, operation: Op::Void })); },
-                                    _ => { return Some(Err(ProcessingError::InvalidElement { elem: Element::new(pos, ElementValue::ProcessorInstruction(name)), info: InvalidElementInfo::ProcessorInstructName })); }
+                                    "void" => { return self.instruct(pos, Op::Void); },
+                                    _ => { return self.err(ProcessingError::InvalidElement { elem: Element::new(pos, ElementValue::ProcessorInstruction(name)), info: InvalidElementInfo::ProcessorInstructName }); }
                                 },
-                            Element { value: ElementValue::Label(name), .. } => { labels.push(name); },
+                            Element { value: ElementValue::Label(name), pos } => { self.labels.push(Label { name, pos }); },
                             elem => { return self.err(ProcessingError::InvalidElement { elem, info: InvalidElementInfo::Unexpected }); }
                         }
                     Err(err) => { return self.err(ProcessingError::ParsingFailure(err)); }
@@ -379,7 +774,7 @@ impl<P, PE> Iterator for Processor<P, PE>
 
 #[derive(Debug)]
 pub enum ProcessingShortcutError {
-    ProcessingError(ProcessingError<ParsingError<LexingError>>),
+    ProcessingErrors(Vec<ProcessingError<ParsingError<LexingError>>>),
     InitializationError(ProcessorInitializationError),
 }
 
@@ -387,7 +782,7 @@ pub enum ProcessingShortcutError {
 impl Display for ProcessingShortcutError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::ProcessingError(_) => write!(f, "while processing an error occurred"),
+            Self::ProcessingErrors(errs) => write!(f, "while processing {} error(s) occurred", errs.len()),
             Self::InitializationError(_) => write!(f, "failed to initialize processor"),
         }
     }
@@ -397,7 +792,7 @@ impl Display for ProcessingShortcutError {
 impl Error for ProcessingShortcutError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
-            Self::ProcessingError(err) => Some(err),
+            Self::ProcessingErrors(errs) => errs.first().map(|err| err as &(dyn Error + 'static)),
             Self::InitializationError(err) => Some(err),
         }
     }
@@ -406,10 +801,10 @@ impl Error for ProcessingShortcutError {
 
 impl Processor<Parser<Lexer<Chars<'_>>, LexingError>, ParsingError<LexingError>> {
     pub fn process(src: &str) -> Result<Vec<Instruct>, ProcessingShortcutError> {
-        Ok(Processor::new(Parser::new(Lexer::new(src.chars())), None, None, false).map_err(ProcessingShortcutError::InitializationError)?.try_collect().map_err(ProcessingShortcutError::ProcessingError)?)
+        collect_all(Processor::new(Parser::new(Lexer::new(src.chars())), None, None, false).map_err(ProcessingShortcutError::InitializationError)?).map_err(ProcessingShortcutError::ProcessingErrors)
     }
 
-    pub fn process_custom(src: &str, lib_path: Option<PathBuf>, rel_path: Option<PathBuf>, allow_abs_paths: bool) -> Result<Vec<Instruct>, ProcessingShortcutError> {
-        Ok(Processor::new(Parser::new(Lexer::new(src.chars())), lib_path, rel_path, allow_abs_paths).map_err(ProcessingShortcutError::InitializationError)?.try_collect().map_err(ProcessingShortcutError::ProcessingError)?)
+    pub fn process_custom(src: &str, lib_path: Option<PathBuf>, rel_path: Option<PathBuf>, allow_abs_paths: bool, defines: Defines, sources: Rc<RefCell<SourceMap>>) -> Result<Vec<Instruct>, ProcessingShortcutError> {
+        collect_all(Processor::new(Parser::new(Lexer::new(src.chars())), lib_path, rel_path, allow_abs_paths).map_err(ProcessingShortcutError::InitializationError)?.with_defines(defines).with_sources(sources)).map_err(ProcessingShortcutError::ProcessingErrors)
     }
 }
@@ -0,0 +1,147 @@
+use std::fmt::{Display, Formatter};
+use watto::{InstructionId, Register};
+use super::{Argument, ValueArgument};
+
+
+// instructions of the assembler itself, each expands into a fixed sequence of cpu instructions
+// so its size is known before any addresses are, references count it as a single instruct
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pseudo {
+    Jump,
+    JumpIfZero,
+    JumpIfNotZero,
+    Call,
+    Return,
+    Move,
+    Sub,
+    ShiftLeft,
+}
+
+
+impl Pseudo {
+    pub const ALL: [Self; 8] = [Self::Jump, Self::JumpIfZero, Self::JumpIfNotZero, Self::Call, Self::Return, Self::Move, Self::Sub, Self::ShiftLeft];
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Self::Jump => "jmp",
+            Self::JumpIfZero => "jz",
+            Self::JumpIfNotZero => "jnz",
+            Self::Call => "call",
+            Self::Return => "ret",
+            Self::Move => "mov",
+            Self::Sub => "sub",
+            Self::ShiftLeft => "shl",
+        }
+    }
+
+    pub fn arguments(self) -> Vec<watto::Argument> {
+        use watto::Argument::{Number, Register};
+        match self {
+            Self::Jump | Self::JumpIfZero | Self::JumpIfNotZero | Self::Call => vec![Number],
+            Self::Return | Self::Sub => vec![],
+            Self::Move => vec![Register, Register],
+            Self::ShiftLeft => vec![Number],
+        }
+    }
+
+    // registers whose value is lost (besides the ones written on purpose, ie $si of a jump)
+    pub fn clobbers(self) -> &'static [Register] {
+        match self {
+            Self::Jump | Self::JumpIfZero | Self::JumpIfNotZero | Self::Return | Self::Move => &[],
+            Self::Call => &[Register::GeneralC],
+            Self::Sub => &[Register::OperandA, Register::OperandB, Register::ServiceStatus],
+            Self::ShiftLeft => &[Register::OperandA, Register::OperandB],
+        }
+    }
+
+    pub fn summary(self) -> &'static str {
+        match self {
+            Self::Jump => "$si = #1",
+            Self::JumpIfZero => "if !$oc: $si = #1",
+            Self::JumpIfNotZero => "if $oc: $si = #1",
+            Self::Call => "$gc = return address, $si = #1",
+            Self::Return => "$si = $gc",
+            Self::Move => "$#1 = $#2",
+            Self::Sub => "$oc = $oa - $ob",
+            Self::ShiftLeft => "$oc = $oa << #1  / #1 has to be a literal within 1..=15",
+        }
+    }
+
+    // cpu instructions it stands for, none if an argument can't be expanded (shift count not a literal)
+    pub fn expand(self, args: &[Argument]) -> Option<Vec<(InstructionId, Vec<Argument>)>> {
+        let reg = |reg| Argument::Register(reg);
+        let lit = |n| Argument::Value(ValueArgument::Literal(n));
+        let si = reg(Register::ServiceInstruction);
+
+        Some(match (self, args) {
+            (Self::Jump, [target]) => vec![(InstructionId::Set, vec![si, target.clone()])],
+            (Self::JumpIfZero, [target]) => vec![(InstructionId::SetIfZero, vec![si, target.clone()])],
+            (Self::JumpIfNotZero, [target]) => vec![(InstructionId::SetIfNotZero, vec![si, target.clone()])],
+            // `~1` is resolved relative to the whole call, so it points right after it
+            (Self::Call, [target]) => vec![
+                (InstructionId::Set, vec![reg(Register::GeneralC), Argument::Value(ValueArgument::Reference(1))]),
+                (InstructionId::Set, vec![si, target.clone()]),
+            ],
+            (Self::Return, []) => vec![(InstructionId::Copy, vec![reg(Register::GeneralC), si])],
+            (Self::Move, [dst, src]) => vec![(InstructionId::Copy, vec![src.clone(), dst.clone()])],
+            // a - b = ~(~a + b), with b kept in $ss meanwhile
+            (Self::Sub, []) => vec![
+                (InstructionId::Copy, vec![reg(Register::OperandB), reg(Register::ServiceStatus)]),
+                (InstructionId::Set, vec![reg(Register::OperandB), lit(0xffff)]),
+                (InstructionId::Xor, vec![]),
+                (InstructionId::Copy, vec![reg(Register::OperandC), reg(Register::OperandA)]),
+                (InstructionId::Copy, vec![reg(Register::ServiceStatus), reg(Register::OperandB)]),
+                (InstructionId::Add, vec![]),
+                (InstructionId::Copy, vec![reg(Register::OperandC), reg(Register::OperandA)]),
+                (InstructionId::Set, vec![reg(Register::OperandB), lit(0xffff)]),
+                (InstructionId::Xor, vec![]),
+            ],
+            // rotations followed by masking off the bits that wrapped around
+            (Self::ShiftLeft, [Argument::Value(ValueArgument::Literal(n @ 1..=15))]) => {
+                let mut instrs = vec![(InstructionId::Rotate, vec![]); *n as usize];
+                instrs.push((InstructionId::Set, vec![reg(Register::OperandB), lit(0xffff << n)]));
+                instrs.push((InstructionId::And, vec![]));
+                instrs
+            },
+            _ => { return None; },
+        })
+    }
+}
+
+
+impl TryFrom<&str> for Pseudo {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::ALL.into_iter().find(|pseudo| pseudo.mnemonic() == value).ok_or(())
+    }
+}
+
+
+impl Display for Pseudo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.mnemonic())
+    }
+}
+
+
+// table of pseudo-instructions in the style of the isa reference
+pub struct PseudoReference;
+
+
+impl Display for PseudoReference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "pseudo (expanded by the assembler):")?;
+        for pseudo in Pseudo::ALL {
+            let args = (1..=pseudo.arguments().len()).map(|i| format!(" #{i}")).collect::<String>();
+            let clobbers = pseudo.clobbers().iter().map(|reg| reg.to_string()).collect::<Vec<_>>().join(" ");
+            if clobbers.is_empty() {
+                writeln!(f, "    {:<11} ; {}", format!("{pseudo}{args}"), pseudo.summary())?;
+            } else {
+                writeln!(f, "    {:<11} ; {}  / clobbers {clobbers}", format!("{pseudo}{args}"), pseudo.summary())?;
+            };
+        };
+
+        Ok(())
+    }
+}
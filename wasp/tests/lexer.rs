@@ -0,0 +1,36 @@
+use wasp::lexer::Lexer;
+use wasp::{assemble, Options};
+
+
+// prefix and value of every word
+fn words(source: &str) -> Vec<(Option<char>, String)> {
+    Lexer::lex(source).unwrap().into_iter().map(|word| (word.prefix(), word.into_value())).collect()
+}
+
+
+#[test]
+fn slash_outside_parentheses_is_a_comment() {
+    let expected = [(None, "set"), (Some('$'), "gd"), (Some('#'), "d10"), (None, "stop")]
+        .map(|(prefix, value)| (prefix, value.to_string()));
+
+    assert_eq!(words("set $gd #d10 / (see isa.txt)\nstop\n"), expected);
+    assert_eq!(words("set $gd #d10 / #1 is the first argument\nstop\n"), expected);
+    assert_eq!(words("set $gd #d10/ #d2\nstop\n"), expected);
+}
+
+
+#[test]
+fn slash_inside_parentheses_is_division() {
+    let words = Lexer::lex("(#d10 / #d2) / #d5\n").unwrap();
+    let operators = words.iter().filter(|word| word.is_operator()).map(|word| word.value()).collect::<Vec<_>>();
+    assert_eq!(operators, ["(", "/", ")"]);
+}
+
+
+#[test]
+fn trailing_comments_assemble() {
+    let source = "set $gd #d10 / (see isa.txt)\nset $ga #d10 / #1 is the first argument\nset $gb (#d10 / #d2)\n";
+    let with_comments = assemble(source, &Options::new()).unwrap();
+    let without = assemble("set $gd #d10\nset $ga #d10\nset $gb #d5\n", &Options::new()).unwrap();
+    assert_eq!(with_comments.bytes(), without.bytes());
+}
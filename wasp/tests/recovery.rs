@@ -0,0 +1,26 @@
+use wasp::{assemble, Options};
+
+
+// headline of every reported error
+fn errors(source: &str) -> Vec<String> {
+    let diags = assemble(source, &Options::new()).err().unwrap();
+    diags.to_string().lines().filter(|line| line.starts_with("error:")).map(String::from).collect()
+}
+
+
+#[test]
+fn unknown_processor_instruct_skips_its_args() {
+    let errors = errors("!foo #d1 #d2\nset $gb #d1\nbogus\n");
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].contains("unknown processor instruct name"));
+    assert!(errors[1].contains("unknown cpu instruction name"));
+}
+
+
+#[test]
+fn missing_operand_stops_at_end_of_line() {
+    let errors = errors("set $ga\n!foo\nset $gb #d1\n");
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].contains("(@1:01 set)"));
+    assert!(errors[1].contains("unknown processor instruct name"));
+}
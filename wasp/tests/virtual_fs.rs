@@ -0,0 +1,62 @@
+use wasp::{assemble, Options, VirtualFs};
+
+
+fn fs() -> VirtualFs {
+    VirtualFs::new()
+        .with_file("util.wts", ":util !bytes #x01 #d2\n")
+        .with_file("sub/inc.wts", ":inc !bytes #x02 #d1\n")
+        .with_file("lib/l.wts", ":l !bytes #x03 #d1\n")
+}
+
+
+#[test]
+fn includes_relative_to_root() {
+    let assembled = assemble("!include \"util.wts\"\n:end !void\n", &Options::new().fs(fs())).unwrap();
+    assert_eq!(assembled.bytes(), [0x01, 0x01]);
+    assert_eq!(assembled.symbol("end"), Some(2));
+}
+
+
+#[test]
+fn includes_relative_to_file() {
+    let source = "!include \"inc.wts\"\n:end !void\n";
+    let assembled = assemble(source, &Options::new().file("sub/main.wts").fs(fs())).unwrap();
+    assert_eq!(assembled.bytes(), [0x02]);
+
+    // order of the options does not matter
+    let assembled = assemble("!include \"util.wts\"\n:end !void\n", &Options::new().fs(fs()).file("main.wts")).unwrap();
+    assert_eq!(assembled.bytes(), [0x01, 0x01]);
+}
+
+
+#[test]
+fn lib_path_in_virtual_fs() {
+    let assembled = assemble("!lib \"l.wts\"\n:end !void\n", &Options::new().fs(fs()).lib_path("lib")).unwrap();
+    assert_eq!(assembled.bytes(), [0x03]);
+    assert_eq!(assembled.symbol("l"), Some(0));
+}
+
+
+#[test]
+fn missing_file_is_reported() {
+    let diags = assemble("!include \"nope.wts\"\n", &Options::new().fs(fs())).err().unwrap();
+    assert_eq!(diags.errors.len(), 1);
+    assert!(diags.to_string().contains("/nope.wts"));
+}
+
+
+#[test]
+fn breaking_out_of_root_is_rejected() {
+    let diags = assemble("!include \"../util.wts\"\n", &Options::new().file("sub/main.wts").fs(fs())).err().unwrap();
+    assert_eq!(diags.errors.len(), 1);
+}
+
+
+#[test]
+fn included_files_are_shown_like_the_main_one() {
+    let fs = fs().with_file("sub/bad.wts", "bogus\n");
+    let diags = assemble("!include \"bad.wts\"\nbogus\n", &Options::new().file("sub/main.wts").fs(fs)).err().unwrap();
+    let report = diags.to_string();
+    assert!(report.contains(" --> sub/main.wts:2:1"));
+    assert!(report.contains(" --> sub/bad.wts:1:1"));
+}
@@ -0,0 +1,78 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use clap::{Parser, ValueEnum};
+use clio::ClioPath;
+
+
+/// a linker for watto objects
+#[derive(Clone, Debug, Parser)]
+pub struct LinkArgs {
+    /// perform a dry run (no writing done)
+    #[arg(long)]
+    pub dry: bool,
+    
+    /// objects to link, each placed right after the previous one unless given an address as `path@0x1000`
+    #[arg(required = true, value_parser = parse_input)]
+    pub objects: Vec<Input>,
+    
+    /// address of the first object not given one
+    #[arg(long, value_parser = parse_addr, default_value = "0")]
+    pub base: u16,
+    
+    /// path to the output
+    #[arg(long, short, value_parser = clap::value_parser!(ClioPath), default_value = "-")]
+    pub out: ClioPath,
+    
+    /// output format
+    #[arg(long, default_value_t)]
+    pub format: Format,
+    
+    /// label at which execution starts (start of the first object if not given)
+    #[arg(long)]
+    pub entry: Option<String>,
+    
+    /// do not embed symbols into the executable
+    #[arg(long, default_value_t = false)]
+    pub strip: bool,
+}
+
+
+#[derive(Clone, Debug)]
+pub struct Input {
+    pub path: PathBuf,
+    pub base: Option<u16>,
+}
+
+
+fn parse_input(s: &str) -> Result<Input, String> {
+    match s.rsplit_once('@') {
+        Some((path, addr)) => Ok(Input { path: PathBuf::from(path), base: Some(parse_addr(addr)?) }),
+        None => Ok(Input { path: PathBuf::from(s), base: None }),
+    }
+}
+
+
+fn parse_addr(s: &str) -> Result<u16, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    }.map_err(|_| format!("not an address: {s}"))
+}
+
+
+#[derive(Clone, Debug, Default, ValueEnum)]
+pub enum Format {
+    Binary,
+    #[default]
+    Executable,
+}
+
+
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Binary => write!(f, "binary"),
+            Self::Executable => write!(f, "executable"),
+        }
+    }
+}
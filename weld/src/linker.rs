@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use watto::{Object, RelocationTarget, Segment, Symbol, ISA_VERSION};
+
+
+// an object along with where it came from and where it has to be placed
+pub struct Unit {
+    pub name: String,
+    pub object: Object,
+    pub base: Option<u16>,
+}
+
+
+pub struct Linked {
+    pub segments: Vec<Segment>,
+    pub symbols: Vec<Symbol>,  // exports first, then labels of every object
+    pub exports: HashMap<String, u16>,
+}
+
+
+impl Linked {
+    pub fn symbol(&self, name: &str) -> Option<u16> {
+        self.exports.get(name).copied()
+            .or_else(|| self.symbols.iter().find(|sym| sym.name == name).map(|sym| sym.addr))
+    }
+}
+
+
+// places every unit (those without an address right after the previous one, the first at `base`),
+// then patches relocations with the final addresses, goes on past errors to report all of them
+pub fn link(units: &[Unit], base: u16) -> Result<Linked, Vec<LinkingError>> {
+    let mut errors = Vec::new();
+
+    let mut bases = Vec::new();
+    let mut next = base as usize;
+    for unit in units.iter() {
+        if unit.object.isa_version != ISA_VERSION {
+            errors.push(LinkingError::IsaMismatch { unit: unit.name.clone(), version: unit.object.isa_version });
+        };
+
+        let addr = unit.base.map_or(next, |base| base as usize);
+        next = addr + unit.object.code.len();
+        if next > 0x1_0000 {
+            errors.push(LinkingError::TooLarge { unit: unit.name.clone() });
+        };
+        bases.push(addr as u16);
+    };
+
+    let mut ranges = units.iter().zip(bases.iter())
+        .filter(|(unit, _)| !unit.object.code.is_empty())
+        .map(|(unit, &base)| (base as usize, base as usize + unit.object.code.len(), &unit.name))
+        .collect::<Vec<_>>();
+    ranges.sort_by_key(|(start, ..)| *start);
+    for pair in ranges.windows(2) {
+        let [(_, end, first), (start, _, second)] = pair else { unreachable!() };
+        if start < end {
+            errors.push(LinkingError::Overlap { first: (*first).clone(), second: (*second).clone() });
+        };
+    };
+
+    let mut exports: HashMap<String, (u16, &str)> = HashMap::new();
+    for (unit, &base) in units.iter().zip(bases.iter()) {
+        for export in unit.object.exports.iter() {
+            if let Some((_, first)) = exports.get(&export.name) {
+                errors.push(LinkingError::DuplicateSymbol { name: export.name.clone(), first: first.to_string(), second: unit.name.clone() });
+            } else {
+                exports.insert(export.name.clone(), (base.wrapping_add(export.addr), &unit.name));
+            };
+        };
+    };
+
+    let mut segments = Vec::new();
+    for (unit, &base) in units.iter().zip(bases.iter()) {
+        let mut code = unit.object.code.clone();
+        for reloc in unit.object.relocations.iter() {
+            let target = match reloc.target {
+                RelocationTarget::Code => base,
+                RelocationTarget::Import(i) => {
+                    let name = &unit.object.imports[i as usize];
+                    match exports.get(name) {
+                        Some((addr, _)) => *addr,
+                        None => {
+                            let err = LinkingError::UndefinedSymbol { name: name.clone(), unit: unit.name.clone() };
+                            if !errors.contains(&err) {
+                                errors.push(err);
+                            };
+                            continue;
+                        },
+                    }
+                },
+            };
+
+            let word = reloc.kind.apply(target.wrapping_add(reloc.addend));
+            code[reloc.offset as usize..][..2].copy_from_slice(&word.to_le_bytes());
+        };
+        segments.push(Segment::new(base, code));
+    };
+
+    if !errors.is_empty() {
+        return Err(errors);
+    };
+
+    let exports = exports.into_iter().map(|(name, (addr, _))| (name, addr)).collect::<HashMap<_, _>>();
+    let mut symbols = exports.iter().map(|(name, &addr)| Symbol { name: name.clone(), addr }).collect::<Vec<_>>();
+    // exports come out of a map in no particular order, the same inputs have to give the same bytes
+    symbols.sort_by(|a, b| (a.addr, &a.name).cmp(&(b.addr, &b.name)));
+    for (unit, &base) in units.iter().zip(bases.iter()) {
+        symbols.extend(unit.object.symbols.iter().flatten().map(|sym| Symbol { name: sym.name.clone(), addr: base.wrapping_add(sym.addr) }));
+    };
+
+    Ok(Linked { segments, symbols, exports })
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkingError {
+    IsaMismatch { unit: String, version: u16 },
+    TooLarge { unit: String },
+    Overlap { first: String, second: String },
+    DuplicateSymbol { name: String, first: String, second: String },
+    UndefinedSymbol { name: String, unit: String },
+}
+
+
+impl Display for LinkingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IsaMismatch { unit, version } => write!(f, "{unit}: assembled for isa version {version}, expected {ISA_VERSION}"),
+            Self::TooLarge { unit } => write!(f, "{unit}: does not fit into address space where placed"),
+            Self::Overlap { first, second } => write!(f, "{second}: overlaps with {first}"),
+            Self::DuplicateSymbol { name, first, second } => write!(f, "{second}: symbol `{name}` is already exported by {first}"),
+            Self::UndefinedSymbol { name, unit } => write!(f, "{unit}: imported symbol `{name}` is not exported by any object"),
+        }
+    }
+}
+
+
+impl Error for LinkingError {}
@@ -0,0 +1,84 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use clap::Parser;
+use watto::{Executable, Object};
+use crate::argparser::Format;
+use crate::linker::{LinkingError, Unit};
+
+mod argparser;
+mod linker;
+
+
+fn handle_error(context: &'static str, mut err: &dyn Error) -> ! {
+    eprintln!("while {context}, an error occurred: {err}");
+
+    while let Some(source) = err.source() {
+        err = source;
+        eprintln!("source of which: {err}");
+    };
+
+    std::process::exit(1)
+}
+
+fn handle_linking_errors(errs: &[LinkingError]) -> ! {
+    for err in errs {
+        eprintln!("error: {err}");
+    };
+    eprintln!("while linking, {} error(s) occurred", errs.len());
+
+    std::process::exit(1)
+}
+
+#[derive(Debug)]
+struct UnknownEntryError(String);
+
+impl Display for UnknownEntryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no such symbol: {}", self.0)
+    }
+}
+
+impl Error for UnknownEntryError {}
+
+
+fn main() {
+    let args: argparser::LinkArgs = argparser::LinkArgs::parse();
+
+    let units = args.objects.iter().map(|input| {
+        let bytes = std::fs::read(&input.path).unwrap_or_else(|err| handle_error("reading object", &err));
+        let object = Object::decode(&bytes).unwrap_or_else(|err| handle_error("decoding object", &err));
+        Unit { name: input.path.display().to_string(), object, base: input.base }
+    }).collect::<Vec<_>>();
+
+    let linked = linker::link(&units, args.base).unwrap_or_else(|errs| handle_linking_errors(&errs));
+
+    let bytes = match args.format {
+        Format::Executable => {
+            let entry = match &args.entry {
+                Some(name) => linked.symbol(name).unwrap_or_else(|| handle_error("resolving entry point", &UnknownEntryError(name.clone()))),
+                None => linked.segments.first().map_or(0x0000, |seg| seg.addr),
+            };
+
+            let mut exe = Executable::new(entry, linked.segments);
+            if !args.strip {
+                exe.symbols = Some(linked.symbols);
+            };
+            exe.encode()
+        },
+        // image of memory from address 0, gaps between objects are zeroed
+        Format::Binary => {
+            let mut image = vec![0; linked.segments.iter().map(|seg| seg.end()).max().unwrap_or(0)];
+            for seg in linked.segments.iter() {
+                image[seg.addr as usize..seg.end()].copy_from_slice(&seg.data);
+            };
+            image
+        },
+    };
+
+    let mut out = args.out.create_with_len(bytes.len() as u64).unwrap_or_else(|err| handle_error("creating output stream", &err));
+
+    if !args.dry {
+        out.write_all(&bytes).unwrap_or_else(|err| handle_error("writing to output", &err));
+    };
+}
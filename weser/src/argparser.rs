@@ -1,6 +1,8 @@
 use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
 use clap::{Args, Parser, ValueEnum};
 use clio::ClioPath;
+use watto::Register;
 
 /// watto cpu emulator with full environment support
 #[derive(Debug, Clone, Parser)]
@@ -30,6 +32,72 @@ pub struct EmuArgs {
     #[arg(long, default_value_t)]
     pub kill_cpu: bool,
     
+    /// run deterministically in virtual time (as fast as possible) until halt or until this many ticks have passed
+    #[arg(long)]
+    pub ticks: Option<u64>,
+    
+    /// register whose lower byte becomes the exit status once cpu stops
+    /// (invalid instruction exits with 125, fault with 126, running out of ticks with 124)
+    #[arg(long, value_parser = parse_register)]
+    pub exit_reg: Option<Register>,
+    
+    /// what cpu does on a fault (eg out-of-bounds memory access)
+    #[arg(long, default_value_t)]
+    pub on_fault: FaultPolicyId,
+    
+    /// where to jump on a fault when raising it as an interrupt (address or symbol)
+    #[arg(long)]
+    pub fault_vector: Option<String>,
+    
+    /// start an interactive debugger on stdin (runs in virtual time, --ticks limits each run)
+    #[arg(long, default_value_t)]
+    pub debug: bool,
+    
+    /// wait for gdb to connect on this localhost port and let it drive the cpu (runs in virtual time, --ticks limits each continue)
+    #[arg(long, conflicts_with = "debug")]
+    pub gdb: Option<u16>,
+    
+    /// how many bytes of host input the serial port holds before the cpu reads them
+    #[arg(long, default_value_t = 16, value_parser = clap::value_parser!(u16).range(1..))]
+    pub serial_buf: u16,
+    
+    /// what happens to host input arriving when the serial port's buffer is full
+    #[arg(long, default_value_t)]
+    pub serial_overflow: OverflowId,
+    
+    /// pass keys to the serial port as they are typed, without echoing them
+    /// (instead of line by line, ctrl-c still quits the emulator, no effect without a serial port)
+    #[arg(long, default_value_t, conflicts_with = "debug")]
+    pub raw_input: bool,
+    
+    /// what time the clock device tells
+    #[arg(long, default_value_t)]
+    pub clock_mode: ClockModeId,
+    
+    /// unix time the clock device is fixed at or starts from (for fixed and virtual modes)
+    #[arg(long, default_value_t = 0)]
+    pub clock_time: u64,
+    
+    /// image file backing the disk device
+    #[arg(long, value_parser = clap::value_parser!(ClioPath).exists().is_file())]
+    pub disk: Option<ClioPath>,
+    
+    /// what happens to sectors the disk device stores
+    #[arg(long, default_value_t)]
+    pub disk_mode: DiskModeId,
+    
+    /// columns and rows of the display device
+    #[arg(long, default_value = "80x25", value_parser = parse_size)]
+    pub display_size: (u8, u8),
+    
+    /// write what the display device shows into this file instead of drawing it in the terminal
+    #[arg(long)]
+    pub display_snapshot: Option<PathBuf>,
+    
+    /// treat the program as a raw binary loaded at address 0 instead of an executable
+    #[arg(long, default_value_t)]
+    pub raw: bool,
+    
     /// path to the program
     #[arg(value_parser = clap::value_parser!(ClioPath).exists().is_file())]
     pub prog: ClioPath,
@@ -39,19 +107,126 @@ pub struct EmuArgs {
 }
 
 
+pub fn parse_register(s: &str) -> Result<Register, String> {
+    Register::try_from(s.strip_prefix('$').unwrap_or(s)).map_err(|()| format!("no such register: {s}"))
+}
+
+
+// `<cols>x<rows>`
+pub fn parse_size(s: &str) -> Result<(u8, u8), String> {
+    let (cols, rows) = s.split_once('x').ok_or_else(|| format!("expected <cols>x<rows>: {s}"))?;
+    let parse = |n: &str| n.parse::<u8>().ok().filter(|n| *n != 0).ok_or_else(|| format!("not a size between 1 and 255: {n}"));
+    Ok((parse(cols)?, parse(rows)?))
+}
+
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum FaultPolicyId {
+    #[default]
+    Halt,
+    Wrap,
+    Interrupt,
+}
+
+impl Display for FaultPolicyId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Halt => write!(f, "halt"),
+            Self::Wrap => write!(f, "wrap"),
+            Self::Interrupt => write!(f, "interrupt"),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum OverflowId {
+    /// new input is lost
+    #[default]
+    Drop,
+    /// oldest buffered input is lost
+    Overwrite,
+    /// input waits with the host until there is room
+    Wait,
+}
+
+impl Display for OverflowId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Drop => write!(f, "drop"),
+            Self::Overwrite => write!(f, "overwrite"),
+            Self::Wait => write!(f, "wait"),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum ClockModeId {
+    /// host's wall clock
+    #[default]
+    Host,
+    /// always --clock-time
+    Fixed,
+    /// starts at --clock-time and advances with the clock device's ticks (deterministic with --ticks)
+    Virtual,
+}
+
+impl Display for ClockModeId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Host => write!(f, "host"),
+            Self::Fixed => write!(f, "fixed"),
+            Self::Virtual => write!(f, "virtual"),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum DiskModeId {
+    /// written back to the image when flushed
+    #[default]
+    ReadWrite,
+    /// refused
+    ReadOnly,
+    /// kept in memory, the image is never changed
+    CopyOnWrite,
+}
+
+impl Display for DiskModeId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ReadWrite => write!(f, "read-write"),
+            Self::ReadOnly => write!(f, "read-only"),
+            Self::CopyOnWrite => write!(f, "copy-on-write"),
+        }
+    }
+}
+
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum DeviceId {
-    // #[value(name = "clock")]
-    // RealtimeClock,
+    #[value(name = "clock")]
+    RealtimeClock,
     #[value(name = "serial")]
-    SerialPort
+    SerialPort,
+    #[value(name = "timer")]
+    Timer,
+    #[value(name = "disk")]
+    BlockDevice,
+    #[value(name = "display")]
+    TextDisplay,
 }
 
 impl Display for DeviceId {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            // Self::RealtimeClock => write!(f, "clock"),
-            Self::SerialPort => write!(f, "serial")
+            Self::RealtimeClock => write!(f, "clock"),
+            Self::SerialPort => write!(f, "serial"),
+            Self::Timer => write!(f, "timer"),
+            Self::BlockDevice => write!(f, "disk"),
+            Self::TextDisplay => write!(f, "display"),
         }
     }
 }
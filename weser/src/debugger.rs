@@ -0,0 +1,220 @@
+use std::io::{BufRead, Write};
+use watto::{Executable, Instruction, Register};
+use system::kernels::{Cpu, Halt};
+use system::{RunOutcome, System};
+use crate::argparser::parse_register;
+use crate::resolve_addr;
+
+
+const HELP: &str = "\
+commands (addresses are decimal, 0x-prefixed hex or symbols):
+    b, break <addr>          set a breakpoint
+    d, delete <addr>         remove a breakpoint
+    bl, breaks               list breakpoints
+    s, step [n]              execute n instructions (default 1)
+    n, next                  step over `!m call`
+    c, continue              run until a breakpoint or halt
+    r, regs                  print registers
+    set <reg> <value>        change a register
+    x, mem <addr> [len]      dump memory (default 64 bytes)
+    w, write <addr> <b>...   write bytes into memory
+    i, instr                 show instruction at $si
+    h, help                  print this
+    q, quit                  exit the debugger
+an empty line repeats the last command";
+
+const DUMP_WIDTH: usize = 16;
+
+
+// repl on stdin, everything it prints goes to stderr to keep program output (eg serial) separate
+pub struct Debugger<'a> {
+    system: System,
+    exe: Option<&'a Executable>,
+    max_ticks: u64,
+}
+
+
+impl<'a> Debugger<'a> {
+    pub fn new(system: System, exe: Option<&'a Executable>, max_ticks: u64) -> Self {
+        Self { system, exe, max_ticks }
+    }
+
+    // returns once user quits or stdin ends, with the halt cpu got to (if any)
+    pub fn run(mut self) -> Option<Halt> {
+        eprintln!("{}", self.location());
+
+        let mut last = String::new();
+        let mut lines = std::io::stdin().lock().lines();
+        loop {
+            eprint!("(wdb) ");
+            let _ = std::io::stderr().flush();
+
+            let Some(Ok(line)) = lines.next() else {
+                break;
+            };
+
+            let line = if line.trim().is_empty() { last.clone() } else { line };
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let Some((&cmd, args)) = words.split_first() else {
+                continue;
+            };
+
+            if matches!(cmd, "q" | "quit") {
+                break;
+            };
+
+            if let Err(err) = self.command(cmd, args) {
+                eprintln!("error: {err}");
+            };
+            last = line;
+        };
+
+        self.system.halt()
+    }
+
+    fn command(&mut self, cmd: &str, args: &[&str]) -> Result<(), String> {
+        match (cmd, args) {
+            ("b" | "break", [addr]) => {
+                let addr = self.addr(addr)?;
+                self.cpu_mut().add_breakpoint(addr);
+                eprintln!("breakpoint at {}", self.describe(addr));
+            },
+            ("d" | "delete", [addr]) => {
+                let addr = self.addr(addr)?;
+                if !self.cpu_mut().remove_breakpoint(addr) {
+                    return Err(format!("no breakpoint at {}", self.describe(addr)));
+                };
+            },
+            ("bl" | "breaks", []) => {
+                for addr in self.cpu().breakpoints() {
+                    eprintln!("    {}", self.describe(addr));
+                };
+            },
+            ("s" | "step", [] | [_]) => {
+                let n = args.first().map_or(Ok(1), |n| n.parse::<u64>().map_err(|err| format!("invalid count {n}: {err}")))?;
+                for _ in 0..n {
+                    self.cpu_mut().resume();
+                    let outcome = self.system.step(self.max_ticks).map_err(|err| err.to_string())?;
+                    if !matches!(outcome, RunOutcome::Stepped { .. }) {
+                        eprintln!("{outcome}");
+                        break;
+                    };
+                };
+                eprintln!("{}", self.location());
+            },
+            ("n" | "next", []) => {
+                self.cpu_mut().resume();
+                let outcome = match call_return(self.cpu()) {
+                    Some(ret) => {
+                        let added = self.cpu_mut().add_breakpoint(ret);
+                        let outcome = self.system.run_until_halt(self.max_ticks).map_err(|err| err.to_string())?;
+                        if added {
+                            self.cpu_mut().remove_breakpoint(ret);
+                        };
+                        outcome
+                    },
+                    None => self.system.step(self.max_ticks).map_err(|err| err.to_string())?,
+                };
+                if !matches!(outcome, RunOutcome::Stepped { .. } | RunOutcome::Breakpoint { .. }) {
+                    eprintln!("{outcome}");
+                };
+                eprintln!("{}", self.location());
+            },
+            ("c" | "continue", []) => {
+                self.cpu_mut().resume();
+                let outcome = self.system.run_until_halt(self.max_ticks).map_err(|err| err.to_string())?;
+                eprintln!("{outcome}");
+                eprintln!("{}", self.location());
+            },
+            ("r" | "regs", []) => {
+                for reg in Register::ALL {
+                    let val = self.cpu().reg(reg);
+                    eprintln!("    {reg:<4} 0x{val:0>4x}  {val:>5}  ({})", reg.description());
+                };
+            },
+            ("set", [reg, val]) => {
+                let reg = parse_register(reg)?;
+                let val = self.addr(val)?;
+                self.cpu_mut().set_reg(reg, val);
+            },
+            ("x" | "mem", [addr] | [addr, _]) => {
+                let addr = self.addr(addr)? as usize;
+                let len = args.get(1).map_or(Ok(64), |len| len.parse::<usize>().map_err(|err| format!("invalid length {len}: {err}")))?;
+                let mem = self.cpu().mem();
+                let bytes = mem.get(addr..addr.saturating_add(len).min(mem.len())).ok_or_else(|| format!("0x{addr:0>4x} is out of ram"))?;
+
+                for (i, row) in bytes.chunks(DUMP_WIDTH).enumerate() {
+                    let hex = row.iter().map(|b| format!("{b:0>2x} ")).collect::<String>();
+                    let text = row.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect::<String>();
+                    eprintln!("0x{:0>4x}  {hex:<width$} |{text}|", addr + i * DUMP_WIDTH, width = DUMP_WIDTH * 3);
+                };
+            },
+            ("w" | "write", [addr, bytes @ ..]) if !bytes.is_empty() => {
+                let addr = self.addr(addr)? as usize;
+                let bytes = bytes.iter()
+                    .map(|b| self.addr(b).and_then(|b| u8::try_from(b).map_err(|_| format!("{b} does not fit into a byte"))))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let mem = self.cpu_mut().mem_mut();
+                mem.get_mut(addr..addr.saturating_add(bytes.len()))
+                    .ok_or_else(|| format!("0x{addr:0>4x} (+{}) is out of ram", bytes.len()))?
+                    .copy_from_slice(&bytes);
+            },
+            ("i" | "instr", []) => eprintln!("{}", self.location()),
+            ("h" | "help", []) => eprintln!("{HELP}"),
+            _ => return Err(format!("unknown command or wrong arguments: {cmd} (see `help`)")),
+        };
+
+        Ok(())
+    }
+
+    fn cpu(&self) -> &Cpu {
+        self.system.cpu().expect("system has no cpu")
+    }
+
+    fn cpu_mut(&mut self) -> &mut Cpu {
+        self.system.cpu_mut().expect("system has no cpu")
+    }
+
+    fn addr(&self, s: &str) -> Result<u16, String> {
+        resolve_addr(s, self.exe).map_err(|err| err.to_string())
+    }
+
+    // `0x0012 <f_main+0x4>`
+    fn describe(&self, addr: u16) -> String {
+        let sym = self.exe
+            .and_then(|exe| exe.symbols.as_ref())
+            .and_then(|symbols| symbols.iter().filter(|sym| sym.addr <= addr).max_by_key(|sym| sym.addr));
+
+        match sym {
+            Some(sym) if sym.addr == addr => format!("0x{addr:0>4x} <{}>", sym.name),
+            Some(sym) => format!("0x{addr:0>4x} <{}+0x{:x}>", sym.name, addr - sym.addr),
+            None => format!("0x{addr:0>4x}"),
+        }
+    }
+
+    fn location(&self) -> String {
+        let cpu = self.cpu();
+        let si = cpu.reg(Register::ServiceInstruction);
+        match cpu.current_instruction() {
+            Ok(instr) => format!("{}: {instr}", self.describe(si)),
+            Err(fault) => format!("{}: ({fault})", self.describe(si)),
+        }
+    }
+}
+
+
+// where execution continues after the call at $si returns, if there is one
+// (`!m call` expands to `set $gc ~2` followed by `set $si <target>`)
+fn call_return(cpu: &Cpu) -> Option<u16> {
+    let si = cpu.reg(Register::ServiceInstruction);
+    let instr = cpu.current_instruction().ok()?;
+    let next = si.wrapping_add(instr.to_id().size() as u16);
+
+    match instr {
+        Instruction::Set(Register::GeneralC, ret)
+            if matches!(cpu.instruction_at(next), Ok(Instruction::Set(Register::ServiceInstruction, _))) => Some(ret),
+        Instruction::Set(Register::ServiceInstruction, _) if cpu.reg(Register::GeneralC) == next => Some(next),
+        _ => None,
+    }
+}
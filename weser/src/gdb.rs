@@ -0,0 +1,323 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use watto::Register;
+use system::kernels::{Cpu, Halt, HaltReason};
+use system::{RunOutcome, System};
+use crate::exit_status;
+
+
+// how many ticks are run between checks for an interrupt (ctrl-c) from gdb
+const RUN_CHUNK: u64 = 10_000;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+const SIGALRM: u8 = 14;
+
+
+// remote serial protocol server for a single gdb connection on localhost,
+// registers are reported in `Register::ALL` order as 16-bit little endian values
+pub struct GdbStub {
+    system: System,
+    stream: TcpStream,
+    max_ticks: u64,
+}
+
+
+// what the session ended with
+enum Detach {
+    Kill,
+    Resume,
+}
+
+
+impl GdbStub {
+    pub fn listen(system: System, port: u16, max_ticks: u64) -> std::io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        eprintln!("waiting for gdb on {}", listener.local_addr()?);
+
+        let (stream, peer) = listener.accept()?;
+        eprintln!("gdb connected from {peer}");
+        stream.set_nodelay(true)?;
+
+        Ok(Self { system, stream, max_ticks })
+    }
+
+    // serves until gdb kills or detaches from the target, on detach the program runs on on its own
+    pub fn serve(mut self) -> std::io::Result<Option<Halt>> {
+        let detach = loop {
+            let Some(packet) = self.read_packet()? else {
+                break Detach::Kill;
+            };
+
+            match packet.as_str() {
+                "k" => break Detach::Kill,
+                "D" => {
+                    self.write_packet("OK")?;
+                    break Detach::Resume;
+                },
+                _ => {
+                    let reply = self.handle(&packet)?;
+                    self.write_packet(&reply)?;
+                },
+            };
+        };
+
+        if let Detach::Resume = detach {
+            let bps = self.cpu().breakpoints().collect::<Vec<_>>();
+            for addr in bps {
+                self.cpu_mut().remove_breakpoint(addr);
+            };
+            self.cpu_mut().resume();
+            self.system.run_until_halt(self.max_ticks).map_err(std::io::Error::other)?;
+        };
+
+        Ok(self.system.halt())
+    }
+
+    fn handle(&mut self, packet: &str) -> std::io::Result<String> {
+        let (cmd, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+
+        Ok(match cmd {
+            "?" => self.stop_reply(),
+            "g" => Register::ALL.iter()
+                .map(|&reg| to_hex(&self.cpu().reg(reg).to_le_bytes()))
+                .collect(),
+            "G" => match from_hex(args) {
+                Some(bytes) if bytes.len() == Register::ALL.len() * 2 => {
+                    for (reg, val) in Register::ALL.into_iter().zip(bytes.chunks(2)) {
+                        self.cpu_mut().set_reg(reg, u16::from_le_bytes([val[0], val[1]]));
+                    };
+                    String::from("OK")
+                },
+                _ => String::from("E01"),
+            },
+            "p" => match parse_num(args).and_then(|n| Register::ALL.get(n)) {
+                Some(&reg) => to_hex(&self.cpu().reg(reg).to_le_bytes()),
+                None => String::from("E01"),
+            },
+            "P" => {
+                let reg = args.split_once('=').and_then(|(n, val)| Some((
+                    *Register::ALL.get(parse_num(n)?)?,
+                    from_hex(val).filter(|val| val.len() == 2)?,
+                )));
+                match reg {
+                    Some((reg, val)) => {
+                        self.cpu_mut().set_reg(reg, u16::from_le_bytes([val[0], val[1]]));
+                        String::from("OK")
+                    },
+                    None => String::from("E01"),
+                }
+            },
+            "m" => {
+                let range = args.split_once(',').and_then(|(addr, len)| Some((parse_num(addr)?, parse_num(len)?)));
+                match range.and_then(|(addr, len)| self.cpu().mem().get(addr..addr.checked_add(len)?)) {
+                    Some(bytes) => to_hex(bytes),
+                    None => String::from("E01"),
+                }
+            },
+            "M" => {
+                let write = args.split_once(':').and_then(|(range, data)| {
+                    let (addr, len) = range.split_once(',')?;
+                    let addr = parse_num(addr)?;
+                    Some((addr, addr.checked_add(parse_num(len)?)?, from_hex(data)?))
+                });
+                match write {
+                    Some((addr, end, data)) if data.len() == end - addr
+                        && let Some(mem) = self.cpu_mut().mem_mut().get_mut(addr..end) => {
+                        mem.copy_from_slice(&data);
+                        String::from("OK")
+                    },
+                    _ => String::from("E01"),
+                }
+            },
+            "Z" | "z" => {
+                let bp = args.strip_prefix("0,")
+                    .and_then(|args| parse_num(args.split(',').next()?))
+                    .and_then(|addr| u16::try_from(addr).ok());
+                match (cmd, bp) {
+                    ("Z", Some(addr)) => { self.cpu_mut().add_breakpoint(addr); String::from("OK") },
+                    ("z", Some(addr)) => { self.cpu_mut().remove_breakpoint(addr); String::from("OK") },
+                    // only software breakpoints are supported
+                    _ => String::new(),
+                }
+            },
+            "s" | "c" => {
+                if let Some(addr) = parse_num(args).and_then(|addr| u16::try_from(addr).ok()) {
+                    self.cpu_mut().set_reg(Register::ServiceInstruction, addr);
+                };
+
+                self.cpu_mut().resume();
+                if cmd == "s" {
+                    self.system.step(self.max_ticks).map_err(std::io::Error::other)?;
+                    self.stop_reply()
+                } else {
+                    self.cont()?
+                }
+            },
+            "H" => String::from("OK"),
+            "q" => self.query(args),
+            _ => String::new(),
+        })
+    }
+
+    fn query(&self, query: &str) -> String {
+        if query.starts_with("Supported") {
+            String::from("PacketSize=4000;qXfer:features:read+")
+        } else if query == "Attached" {
+            String::from("1")
+        } else if let Some(range) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            let xml = target_xml();
+            let Some((offset, end)) = range.split_once(',').and_then(|(offset, len)| {
+                let offset = parse_num(offset)?;
+                Some((offset, offset.checked_add(parse_num(len)?)?))
+            }) else {
+                return String::from("E01");
+            };
+
+            let chunk = xml.get(offset.min(xml.len())..end.min(xml.len())).unwrap_or_default();
+            if end >= xml.len() {
+                format!("l{chunk}")
+            } else {
+                format!("m{chunk}")
+            }
+        } else {
+            String::new()
+        }
+    }
+
+    // runs until a breakpoint, halt, tick budget or interrupt from gdb
+    fn cont(&mut self) -> std::io::Result<String> {
+        let mut ticks = 0;
+        while ticks < self.max_ticks {
+            let outcome = self.system.run_until_halt(RUN_CHUNK.min(self.max_ticks - ticks)).map_err(std::io::Error::other)?;
+            ticks += outcome.ticks();
+
+            if !matches!(outcome, RunOutcome::BudgetExhausted { .. }) {
+                return Ok(self.stop_reply());
+            };
+
+            if self.interrupted()? {
+                return Ok(format!("S{SIGINT:0>2x}"));
+            };
+        };
+
+        Ok(format!("S{SIGALRM:0>2x}"))
+    }
+
+    fn stop_reply(&self) -> String {
+        match self.system.halt() {
+            Some(Halt { reason: HaltReason::Fault(fault), .. }) if fault.kind.is_invalid_instruction() => format!("S{SIGILL:0>2x}"),
+            Some(Halt { reason: HaltReason::Fault(_), .. }) => format!("S{SIGSEGV:0>2x}"),
+            Some(halt) => format!("W{:0>2x}", exit_status(&halt)),
+            None => format!("S{SIGTRAP:0>2x}"),
+        }
+    }
+
+    fn interrupted(&mut self) -> std::io::Result<bool> {
+        self.stream.set_nonblocking(true)?;
+        let mut byte = [0];
+        let read = self.stream.read(&mut byte);
+        self.stream.set_nonblocking(false)?;
+
+        match read {
+            Ok(1) => Ok(byte[0] == 0x03),
+            Ok(_) => Err(ErrorKind::UnexpectedEof.into()),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    // next packet's data (acknowledged), none once gdb hangs up
+    fn read_packet(&mut self) -> std::io::Result<Option<String>> {
+        let mut byte = [0];
+        loop {
+            if self.stream.read(&mut byte)? == 0 {
+                return Ok(None);
+            };
+
+            match byte[0] {
+                b'$' => break,
+                // a stray interrupt while the target is stopped is answered right away
+                0x03 => { self.write_packet(&self.stop_reply())?; },
+                _ => {},
+            };
+        };
+
+        let mut data = Vec::new();
+        loop {
+            if self.stream.read(&mut byte)? == 0 {
+                return Ok(None);
+            };
+
+            match byte[0] {
+                b'#' => break,
+                b => data.push(b),
+            };
+        };
+
+        let mut checksum = [0; 2];
+        self.stream.read_exact(&mut checksum)?;
+
+        let valid = from_hex(&String::from_utf8_lossy(&checksum)).is_some_and(|cs| cs[0] == checksum_of(&data));
+        self.stream.write_all(if valid { b"+" } else { b"-" })?;
+
+        if valid {
+            Ok(Some(String::from_utf8_lossy(&data).into_owned()))
+        } else {
+            self.read_packet()
+        }
+    }
+
+    fn write_packet(&mut self, data: &str) -> std::io::Result<()> {
+        write!(self.stream, "${data}#{:0>2x}", checksum_of(data.as_bytes()))?;
+        self.stream.flush()
+    }
+
+    fn cpu(&self) -> &Cpu {
+        self.system.cpu().expect("system has no cpu")
+    }
+
+    fn cpu_mut(&mut self) -> &mut Cpu {
+        self.system.cpu_mut().expect("system has no cpu")
+    }
+}
+
+
+fn target_xml() -> String {
+    let regs = Register::ALL.iter().enumerate()
+        .map(|(i, reg)| {
+            let ty = if *reg == Register::ServiceInstruction { "code_ptr" } else { "uint16" };
+            format!("<reg name=\"{}\" bitsize=\"16\" type=\"{ty}\" regnum=\"{i}\"/>", reg.to_string().trim_start_matches('$'))
+        })
+        .collect::<String>();
+
+    format!("<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\"><target version=\"1.0\"><feature name=\"org.watto.cpu\">{regs}</feature></target>")
+}
+
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |acc, &b| acc.wrapping_add(b))
+}
+
+
+fn parse_num(s: &str) -> Option<usize> {
+    usize::from_str_radix(s, 16).ok()
+}
+
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:0>2x}")).collect()
+}
+
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    };
+
+    (0..s.len()).step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -1,43 +1,202 @@
 #![feature(let_chains)]
 
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 use clap::Parser;
-use crate::argparser::DeviceId;
-use system::kernels::{Cpu, Serial};
-use system::{DeviceDescription, System};
+use watto::Executable;
+use crate::argparser::{ClockModeId, DeviceId, DiskModeId, FaultPolicyId, OverflowId};
+use crate::debugger::Debugger;
+use crate::gdb::GdbStub;
+use system::kernels::{stdin_input, BlockDevice, ClockMode, Cpu, DeviceKernel, DiskMode, DisplayOutput, FaultPolicy, Halt, HaltReason, Overflow, RealtimeClock, Serial, TextDisplay, Timer};
+use system::{DeviceDescription, RunOutcome, System};
 
 mod argparser;
+mod debugger;
+mod gdb;
+mod terminal;
+
+
+fn handle_error(context: &'static str, mut err: &dyn Error) -> ! {
+    terminal::restore();
+    eprintln!("while {context}, an error occurred: {err}");
+    
+    while let Some(source) = err.source() {
+        err = source;
+        eprintln!("source of which: {err}");
+    };
+    
+    std::process::exit(1)
+}
+
+const EXIT_TIMEOUT: i32 = 124;
+const EXIT_INVALID_INSTRUCTION: i32 = 125;
+const EXIT_FAULT: i32 = 126;
+
+
+fn exit_status(halt: &Halt) -> i32 {
+    match &halt.reason {
+        HaltReason::Stopped | HaltReason::Paused => halt.exit_code.map_or(0, |code| code as u8 as i32),
+        HaltReason::Fault(fault) if fault.kind.is_invalid_instruction() => EXIT_INVALID_INSTRUCTION,
+        HaltReason::Fault(_) => EXIT_FAULT,
+    }
+}
+
+
+#[derive(Debug)]
+struct UnknownSymbolError(String);
+
+impl Display for UnknownSymbolError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "not an address nor a known symbol: {}", self.0)
+    }
+}
+
+impl Error for UnknownSymbolError {}
+
+
+#[derive(Debug)]
+struct MissingDiskError;
+
+impl Display for MissingDiskError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no image given, see --disk")
+    }
+}
+
+impl Error for MissingDiskError {}
+
+
+fn resolve_addr(s: &str, exe: Option<&Executable>) -> Result<u16, UnknownSymbolError> {
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    };
+    
+    parsed
+        .or_else(|| exe?.symbol(s))
+        .ok_or_else(|| UnknownSymbolError(s.to_string()))
+}
 
 
 fn main() {
     let emu_args: argparser::EmuArgs = argparser::EmuArgs::parse();
     
     let prog = emu_args.prog.read_all().unwrap().into_vec();
+    let (cpu, exe) = if emu_args.raw {
+        (Cpu::new(emu_args.ram_size, &prog), None)
+    } else {
+        let exe = Executable::decode(&prog).unwrap_or_else(|err| handle_error("decoding executable", &err));
+        (Cpu::from_executable(emu_args.ram_size, &exe).unwrap_or_else(|err| handle_error("loading executable", &err)), Some(exe))
+    };
+    
+    let fault_policy = match emu_args.on_fault {
+        FaultPolicyId::Halt => FaultPolicy::Halt,
+        FaultPolicyId::Wrap => FaultPolicy::Wrap,
+        FaultPolicyId::Interrupt => FaultPolicy::Interrupt {
+            vector: resolve_addr(emu_args.fault_vector.as_deref().unwrap_or("0"), exe.as_ref()).unwrap_or_else(|err| handle_error("resolving fault vector", &err)),
+        },
+    };
+    
     let mut devs = vec![
         DeviceDescription::new(
             0x00,
-            Cpu::new(emu_args.ram_size, &prog),
+            cpu.with_exit_reg(emu_args.exit_reg).with_fault_policy(fault_policy),
             emu_args.clock_freq,
             emu_args.verbose,
         )
     ];
     
+    let overflow = match emu_args.serial_overflow {
+        OverflowId::Drop => Overflow::Drop,
+        OverflowId::Overwrite => Overflow::Overwrite,
+        OverflowId::Wait => Overflow::Wait,
+    };
+    
+    let devs_clock_freq = emu_args.clock_freq.div_ceil(emu_args.devs_clocks_freq_coef);
+    let clock_mode = match emu_args.clock_mode {
+        ClockModeId::Host => ClockMode::Host,
+        ClockModeId::Fixed => ClockMode::Fixed(emu_args.clock_time),
+        ClockModeId::Virtual => ClockMode::Virtual { start: emu_args.clock_time, freq: devs_clock_freq },
+    };
+    
+    let disk_mode = match emu_args.disk_mode {
+        DiskModeId::ReadWrite => DiskMode::ReadWrite,
+        DiskModeId::ReadOnly => DiskMode::ReadOnly,
+        DiskModeId::CopyOnWrite => DiskMode::CopyOnWrite,
+    };
+    
+    // host stdin goes to the first serial port (if there is one), unless debugger reads commands from it
+    let has_serial = emu_args.device.iter().any(|dev| matches!(dev, DeviceId::SerialPort));
+    let mut input = (has_serial && !emu_args.debug).then(|| terminal::interruptible(stdin_input()));
+    let raw_input = emu_args.raw_input && input.is_some();
+    
     devs.extend(emu_args.device.into_iter().enumerate().map(|(i, dev)|
         DeviceDescription::new(
             i as u8 + 2,
             match dev {
-                DeviceId::SerialPort => Serial::new()
+                DeviceId::SerialPort => DeviceKernel::from(match input.take() {
+                    Some(input) => Serial::new().with_input(input).with_input_buf(emu_args.serial_buf as usize, overflow),
+                    None => Serial::new(),
+                }),
+                DeviceId::RealtimeClock => DeviceKernel::from(RealtimeClock::new(clock_mode)),
+                DeviceId::Timer => DeviceKernel::from(Timer::new()),
+                DeviceId::BlockDevice => {
+                    let path = emu_args.disk.as_ref().unwrap_or_else(|| handle_error("attaching disk", &MissingDiskError));
+                    DeviceKernel::from(BlockDevice::open(path, disk_mode).unwrap_or_else(|err| handle_error("opening disk image", &err)))
+                },
+                DeviceId::TextDisplay => {
+                    let output = emu_args.display_snapshot.clone().map_or(DisplayOutput::Terminal, DisplayOutput::Snapshot);
+                    DeviceKernel::from(TextDisplay::new(emu_args.display_size.0, emu_args.display_size.1, output))
+                },
             },
-            emu_args.clock_freq.div_ceil(emu_args.devs_clocks_freq_coef),
+            devs_clock_freq,
             emu_args.verbose,
         )
     ));
     
     
-    let mut system = System::new(devs, emu_args.clock_freq.div_ceil(emu_args.devs_clocks_freq_coef));
+    let mut system = System::new(devs, devs_clock_freq);
+    
+    if raw_input {
+        terminal::enable_raw_input().unwrap_or_else(|err| handle_error("switching terminal to raw input", &err));
+    };
     
-    if emu_args.kill_cpu {
-        system.run_and_kill_cpu(None);
+    // either how cpu halted or the exit status when it did not
+    let halt = if emu_args.debug {
+        match Debugger::new(system, exe.as_ref(), emu_args.ticks.unwrap_or(u64::MAX)).run() {
+            Some(halt) => Ok(halt),
+            None => Err(0),
+        }
+    } else if let Some(port) = emu_args.gdb {
+        let halt = GdbStub::listen(system, port, emu_args.ticks.unwrap_or(u64::MAX))
+            .and_then(GdbStub::serve)
+            .unwrap_or_else(|err| handle_error("serving gdb", &err));
+        
+        match halt {
+            Some(halt) => Ok(halt),
+            None => Err(0),
+        }
+    } else if let Some(ticks) = emu_args.ticks {
+        let outcome = system.run_until_halt(ticks).unwrap_or_else(|err| handle_error("scheduling devices", &err));
+        eprintln!("{outcome}");
+        
+        match outcome {
+            RunOutcome::Halted { halt, .. } => Ok(halt),
+            RunOutcome::BudgetExhausted { .. } => Err(EXIT_TIMEOUT),
+            RunOutcome::Breakpoint { .. } | RunOutcome::Stepped { .. } => unreachable!("no breakpoints outside of debugger"),
+        }
+    } else if emu_args.kill_cpu {
+        Ok(system.run_and_kill_cpu(None).unwrap())
     } else {
-        system.run(None);
+        Ok(system.run(None).unwrap())
     };
+    
+    terminal::restore();
+    let halt = halt.unwrap_or_else(|status| std::process::exit(status));
+    
+    if !matches!(halt.reason, HaltReason::Stopped | HaltReason::Paused) {
+        eprintln!("cpu halted: {halt}");
+    };
+    
+    std::process::exit(exit_status(&halt));
 }
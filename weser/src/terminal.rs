@@ -0,0 +1,60 @@
+use std::io;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver};
+
+const INTERRUPT: u8 = 0x03;
+const EXIT_INTERRUPTED: i32 = 130;
+
+// settings of the terminal from before raw input got enabled
+static SAVED: Mutex<Option<String>> = Mutex::new(None);
+
+
+// host terminal passes keys on as they are typed and does not echo them,
+// nor does it interrupt on ctrl-c, which gets to `interruptible` as input instead
+pub fn enable_raw_input() -> io::Result<()> {
+    let saved = stty(&["-g"])?;
+    stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+    *SAVED.lock().unwrap() = Some(saved.trim().to_string());
+    Ok(())
+}
+
+
+// puts the terminal back the way it was (if it was changed at all), has to happen before every exit
+pub fn restore() {
+    if let Some(saved) = SAVED.lock().unwrap().take() {
+        let _ = stty(&[&saved]);
+    };
+}
+
+
+// passes input on, except for ctrl-c while in raw input which quits the emulator
+pub fn interruptible(input: Receiver<u8>) -> Receiver<u8> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for b in input {
+            if b == INTERRUPT && SAVED.lock().unwrap().is_some() {
+                restore();
+                std::process::exit(EXIT_INTERRUPTED);
+            };
+            if tx.send(b).is_err() {
+                break;
+            };
+        };
+    });
+    rx
+}
+
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let out = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()?;
+
+    if !out.status.success() {
+        return Err(io::Error::other("stty failed, is stdin a terminal?"));
+    };
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}